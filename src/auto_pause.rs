@@ -0,0 +1,249 @@
+use crate::config::{AutoPauseConfig, ReturnAction};
+use crate::focus;
+use crate::logind::LoginSessionProxy;
+use crate::session::{Command, SessionHandle};
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use log::debug;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use zbus::Connection;
+
+/// Pauses the timer while the screen is locked or the user is idle.
+#[derive(Clone, Debug)]
+pub struct AutoPause {
+    /// Whether to pause when the screen is locked.
+    on_lock: bool,
+    /// Whether to pause when the user is idle.
+    on_idle: bool,
+    /// Whether to resume the timer when the user returns.
+    resume: bool,
+    /// How long the user has to be away before the timer is paused.
+    threshold: Duration,
+}
+
+impl AutoPause {
+    /// Creates a new `AutoPause` from its configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The auto-pause configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AutoPause`, or an error if the threshold is invalid.
+    pub fn from_config(config: &AutoPauseConfig) -> Result<Self> {
+        let threshold = match &config.threshold {
+            Some(threshold) => focus::parse_duration(threshold)
+                .ok_or_else(|| anyhow::anyhow!("Invalid auto-pause threshold: '{threshold}'"))?,
+            None => Duration::ZERO,
+        };
+        Ok(Self {
+            on_lock: config.on_lock.unwrap_or(true),
+            on_idle: config.on_idle.unwrap_or(true),
+            resume: config.on_return.unwrap_or_default() == ReturnAction::Resume,
+            threshold,
+        })
+    }
+
+    /// Starts watching the login session in the background.
+    ///
+    /// The screen counts as locked between the `Lock` and `Unlock` signals of the session, or
+    /// while its `LockedHint` is set. The `Lock` signal is only emitted by `loginctl
+    /// lock-session`, so a screen locker started directly, e.g. by swayidle's `timeout` hook,
+    /// is only noticed through the locked hint. Nothing emits `Unlock` when swaylock exits, so
+    /// the lock also ends when the locked hint is reset.
+    ///
+    /// When the user has been away for longer than the threshold, a [`Command::Pause`] is sent,
+    /// which pauses the timer from the moment the user left. When the user returns, a
+    /// [`Command::Resume`] is sent if the timer should be resumed.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection to the bus of logind, usually the system bus.
+    /// * `session` - The session to pause.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the background task, which has to be aborted at the end of the session.
+    pub fn start(&self, connection: Connection, session: SessionHandle) -> JoinHandle<()> {
+        let auto_pause = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = auto_pause.watch(&connection, &session).await {
+                eprintln!("Watching the login session failed, auto-pause is disabled: {e}");
+            }
+        })
+    }
+
+    async fn watch(&self, connection: &Connection, session: &SessionHandle) -> Result<()> {
+        let login_session = LoginSessionProxy::new(connection).await?;
+        let mut lock = login_session.receive_lock().await?;
+        let mut unlock = login_session.receive_unlock().await?;
+        let mut idle = login_session.receive_idle_hint_changed().await;
+        let mut locked_hint = login_session.receive_locked_hint_changed().await;
+
+        let (mut locked, mut idle_hint) = (false, false);
+        // The time when the user left, and whether the timer was paused since then
+        let mut away_since: Option<Instant> = None;
+        let mut paused = false;
+        loop {
+            let deadline = away_since
+                .filter(|_| !paused)
+                .map(|since| since + self.threshold);
+            tokio::select! {
+                Some(_) = lock.next(), if self.on_lock => locked = true,
+                Some(_) = unlock.next(), if self.on_lock => locked = false,
+                Some(change) = locked_hint.next(), if self.on_lock => locked = change.get().await?,
+                Some(change) = idle.next(), if self.on_idle => idle_hint = change.get().await?,
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let since = away_since.unwrap_or_else(Instant::now);
+                    debug!("User away since {:?}, pausing", since.elapsed());
+                    session.send(Command::Pause(since.into_std())).await?;
+                    paused = true;
+                    continue;
+                },
+                else => break,
+            }
+
+            let away = locked || idle_hint;
+            debug!("Login session changed: locked = {locked}, idle = {idle_hint}");
+            if away && away_since.is_none() {
+                away_since = Some(Instant::now());
+            } else if !away && away_since.is_some() {
+                away_since = None;
+                if paused && self.resume {
+                    debug!("User returned, resuming");
+                    session.send(Command::Resume).await?;
+                }
+                paused = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{Request, SessionState};
+    use crate::timer::Timer;
+    use tokio::sync::{mpsc, watch};
+    use zbus::object_server::SignalEmitter;
+
+    const PATH: &str = "/org/freedesktop/login1/session/auto";
+
+    /// A stand-in logind session, which emits the lock signals on request.
+    struct Session {
+        locked: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl Session {
+        #[zbus(signal)]
+        async fn lock(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn unlock(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn idle_hint(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn locked_hint(&self) -> bool {
+            self.locked
+        }
+    }
+
+    /// Emits a signal until the watcher reacts to it, as the watcher subscribes asynchronously.
+    async fn expect_command(
+        emit: impl AsyncFn() -> zbus::Result<()>,
+        commands: &mut mpsc::Receiver<Request>,
+    ) -> Command {
+        for _ in 0..50 {
+            emit().await.unwrap();
+            tokio::select! {
+                Some((command, reply)) = commands.recv() => {
+                    let state = SessionState::new(Timer::new(Duration::from_secs(60)));
+                    let _ = reply.send(Ok(state));
+                    return command;
+                },
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+            }
+        }
+        panic!("No command received");
+    }
+
+    /// Serves a stand-in session and starts watching it with the given configuration.
+    async fn start(
+        config: &str,
+    ) -> (
+        zbus::Connection,
+        mpsc::Receiver<Request>,
+        JoinHandle<()>,
+        watch::Sender<SessionState>,
+    ) {
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let (server, client) = tokio::try_join!(
+            zbus::connection::Builder::unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at(PATH, Session { locked: false })
+                .unwrap()
+                .build(),
+            zbus::connection::Builder::unix_stream(client).p2p().build(),
+        )
+        .unwrap();
+
+        let config: AutoPauseConfig = toml::from_str(config).unwrap();
+        let state = SessionState::new(Timer::new(Duration::from_secs(60)));
+        let (session, commands, state_tx) = SessionHandle::new(state);
+        let task = AutoPause::from_config(&config)
+            .unwrap()
+            .start(client, session);
+        (server, commands, task, state_tx)
+    }
+
+    #[tokio::test]
+    async fn test_pause_on_lock() {
+        let (server, mut commands, task, _state_tx) = start("on-idle = false").await;
+        let emitter = SignalEmitter::new(&server, PATH).unwrap();
+
+        let before = std::time::Instant::now();
+        let lock = expect_command(async || Session::lock(&emitter).await, &mut commands);
+        let Command::Pause(since) = lock.await else {
+            panic!("Expected a pause command");
+        };
+        assert!(since >= before);
+        let unlock = expect_command(async || Session::unlock(&emitter).await, &mut commands);
+        assert_eq!(unlock.await, Command::Resume);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pause_on_locked_hint() {
+        let (server, mut commands, task, _state_tx) = start("on-idle = false").await;
+        let session = server
+            .object_server()
+            .interface::<_, Session>(PATH)
+            .await
+            .unwrap();
+        let set_locked = async |locked: bool| {
+            let mut mock = session.get_mut().await;
+            mock.locked = locked;
+            mock.locked_hint_changed(session.signal_emitter()).await
+        };
+
+        let lock = expect_command(async || set_locked(true).await, &mut commands);
+        assert!(matches!(lock.await, Command::Pause(_)));
+        // The screen locker exited without an `Unlock` signal
+        let unlock = expect_command(async || set_locked(false).await, &mut commands);
+        assert_eq!(unlock.await, Command::Resume);
+        task.abort();
+    }
+}
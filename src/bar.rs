@@ -0,0 +1,162 @@
+use crate::config::ExternalBarConfig;
+use crate::process;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use tokio::process::Command;
+
+/// The mechanism used to hide and show an external bar.
+#[derive(Clone, Debug)]
+enum Backend {
+    /// Toggle the bar by sending a signal to every process with the given name.
+    Signal { process: String, signal: i32 },
+    /// Hide and show the bar by running shell commands.
+    Command { hide: String, show: String },
+}
+
+/// Tracks what has been done to hide the bar, so that exactly this can be undone.
+#[derive(Clone, Debug)]
+enum Hidden {
+    /// The toggle signal was delivered to these PIDs.
+    Signaled(Vec<i32>),
+    /// The hide command ran successfully.
+    Commanded,
+}
+
+/// A status bar that is not managed by sway, e.g. Waybar.
+///
+/// A toggle signal flips the visibility of a bar without reporting the new state. To restore the
+/// bar exactly, the bar keeps track of the processes it toggled and only toggles those back which
+/// are still running. A restarted bar process starts visible and is left alone.
+#[derive(Clone, Debug)]
+pub struct ExternalBar {
+    backend: Backend,
+    hidden: Option<Hidden>,
+}
+
+impl ExternalBar {
+    /// Creates a new `ExternalBar` from its configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the bar.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ExternalBar`, or an error if the configuration does not describe
+    /// exactly one of the signal or the command backend.
+    pub fn from_config(config: &ExternalBarConfig) -> Result<Self> {
+        let backend = match config {
+            ExternalBarConfig {
+                process: Some(process),
+                signal,
+                hide_command: None,
+                show_command: None,
+            } => {
+                let signal = signal.as_deref().unwrap_or("SIGUSR1");
+                Backend::Signal {
+                    process: process.clone(),
+                    signal: process::parse_signal(signal)
+                        .ok_or_else(|| anyhow!("Invalid signal for bar '{process}': '{signal}'"))?,
+                }
+            }
+            ExternalBarConfig {
+                process: None,
+                signal: None,
+                hide_command: Some(hide),
+                show_command: Some(show),
+            } => Backend::Command {
+                hide: hide.clone(),
+                show: show.clone(),
+            },
+            _ => {
+                return Err(anyhow!(
+                    "Invalid bar configuration {config:?}: \
+                     specify either `process` (and `signal`) or `hide-command` and `show-command`"
+                ))
+            }
+        };
+        Ok(Self {
+            backend,
+            hidden: None,
+        })
+    }
+
+    /// Hides the bar. Does nothing if the bar is already hidden.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn hide(&mut self) -> Result<()> {
+        if self.hidden.is_some() {
+            return Ok(());
+        }
+        match &self.backend {
+            Backend::Signal { process, signal } => {
+                let pids = process::find_by_name(process)?;
+                if pids.is_empty() {
+                    warn!("No running process '{process}' found, bar not hidden");
+                    return Ok(());
+                }
+                let mut signaled = Vec::new();
+                for pid in pids {
+                    match process::send_signal(pid, *signal) {
+                        Ok(()) => signaled.push(pid),
+                        Err(e) => warn!("Failed to signal bar process {pid}: {e}"),
+                    }
+                }
+                debug!("Hid bar '{process}' in processes {signaled:?}");
+                self.hidden = Some(Hidden::Signaled(signaled));
+            }
+            Backend::Command { hide, .. } => {
+                run_shell(hide).await?;
+                self.hidden = Some(Hidden::Commanded);
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the bar again, if it was hidden by [`ExternalBar::hide`].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation, the first error if signaling
+    /// one of the bar processes failed.
+    pub async fn show(&mut self) -> Result<()> {
+        let Some(hidden) = self.hidden.take() else {
+            return Ok(());
+        };
+        match (&self.backend, hidden) {
+            (Backend::Signal { process, signal }, Hidden::Signaled(pids)) => {
+                let running = process::find_by_name(process)?;
+                // Every process is shown again, even if signaling another one failed
+                let mut result = Ok(());
+                for pid in pids {
+                    if !running.contains(&pid) {
+                        debug!("Bar process {pid} has exited, not toggling it back");
+                        continue;
+                    }
+                    if let Err(e) = process::send_signal(pid, *signal) {
+                        warn!("Failed to signal bar process {pid}: {e}");
+                        result = result.and(Err(e));
+                    }
+                }
+                result?;
+            }
+            (Backend::Command { show, .. }, Hidden::Commanded) => run_shell(show).await?,
+            _ => unreachable!("bar hidden with a different backend"),
+        }
+        Ok(())
+    }
+}
+
+/// Runs a command with `sh -c` and fails if it does not exit successfully.
+async fn run_shell(command: &str) -> Result<()> {
+    debug!("Running command: {command}");
+    let status = Command::new("sh").arg("-c").arg(command).status().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("Command `{command}` failed with {status}"))
+    }
+}
@@ -0,0 +1,405 @@
+use crate::sway_ipc_interface::SwayIpcInterface;
+
+use anyhow::{anyhow, Result};
+use log::{debug, trace, warn};
+use std::collections::BTreeMap;
+
+/// The binding mode sway starts in, which holds the bindings outside of `mode` blocks.
+const DEFAULT_MODE: &str = "default";
+
+/// A binding mode and key bindings, which are only active during the focus session.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    /// Binding mode to switch to.
+    mode: Option<String>,
+    /// Key bindings, mapping keys to sway commands.
+    bindings: BTreeMap<String, String>,
+}
+
+/// The original binding mode and key bindings, used to restore them after the session.
+pub struct BindingsSession {
+    /// The binding mode before the session, if the session switched to its own mode.
+    mode: Option<String>,
+    /// The binding mode the session key bindings were added to.
+    bound_mode: String,
+    /// Keys bound for the session, with the binding of the sway config they replaced, if any.
+    bindings: Vec<(String, Option<ConfigBinding>)>,
+}
+
+/// A `bindsym` of the sway config.
+#[derive(Clone, Debug, PartialEq)]
+struct ConfigBinding {
+    /// The binding mode of the binding.
+    mode: String,
+    /// Flags of the binding, e.g. `--to-code`.
+    flags: Vec<String>,
+    /// The key combination, with variables like `$mod` replaced.
+    key: String,
+    /// The sway command.
+    command: String,
+}
+
+impl Bindings {
+    /// Creates new `Bindings`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Binding mode to switch to during the session.
+    /// * `bindings` - Key bindings for the session.
+    pub fn new(mode: Option<String>, bindings: BTreeMap<String, String>) -> Self {
+        Self { mode, bindings }
+    }
+
+    /// Switches to the binding mode and registers the key bindings.
+    ///
+    /// The key bindings are added to the binding mode which is active at that moment, which is the
+    /// session binding mode if one is configured. Existing bindings of the same keys in that mode
+    /// are looked up in the sway config to restore them afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BindingsSession` to restore the previous state with, or an error
+    /// if the binding mode is not defined in the sway config.
+    pub async fn apply(&self, sway: &mut SwayIpcInterface) -> Result<BindingsSession> {
+        if let Some(session_mode) = &self.mode {
+            if !sway.get_binding_modes().await?.contains(session_mode) {
+                return Err(anyhow!(
+                    "Binding mode '{session_mode}' is not defined in the sway config"
+                ));
+            }
+        }
+        let current = sway.get_binding_mode().await?;
+        let mut session = BindingsSession {
+            mode: self.mode.as_ref().map(|_| current.clone()),
+            bound_mode: self.mode.clone().unwrap_or(current),
+            bindings: Vec::new(),
+        };
+
+        if let Err(e) = self.apply_changes(sway, &mut session).await {
+            if let Err(restore_error) = session.restore(sway).await {
+                warn!("Failed to restore the key bindings: {restore_error:#}");
+            }
+            return Err(e);
+        }
+        Ok(session)
+    }
+
+    /// Switches to the binding mode and registers the key bindings, recording every binding in
+    /// the session as soon as it is made, so that a failure halfway can be restored.
+    async fn apply_changes(
+        &self,
+        sway: &mut SwayIpcInterface,
+        session: &mut BindingsSession,
+    ) -> Result<()> {
+        if let Some(session_mode) = &self.mode {
+            sway.set_binding_mode(session_mode).await?;
+        }
+
+        let sway_config = sway.get_config().await?;
+        let config_bindings = parse_bindings(&sway_config);
+        let variables = parse_variables(&sway_config);
+        for (key, command) in &self.bindings {
+            let key_combo = normalize_key(&replace_variables(key, &variables));
+            let original = config_bindings
+                .iter()
+                .rev()
+                .find(|binding| {
+                    binding.mode == session.bound_mode && normalize_key(&binding.key) == key_combo
+                })
+                .cloned();
+            sway.bindsym(&[], key, command).await?;
+            session.bindings.push((key.clone(), original));
+        }
+        Ok(())
+    }
+}
+
+impl BindingsSession {
+    /// Removes the session key bindings, restores the bindings they replaced and switches back to
+    /// the previous binding mode. The bindings are changed in the mode they were added to, even if
+    /// another mode is active now. Every step is attempted even if another one fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn restore(self, sway: &mut SwayIpcInterface) -> Result<()> {
+        let mut failures = Vec::new();
+        let current = match sway.get_binding_mode().await {
+            Ok(mode) => Some(mode),
+            Err(e) => {
+                failures.push(format!("current binding mode: {e}"));
+                None
+            }
+        };
+        let mut active = current.clone();
+        if !self.bindings.is_empty() && active.as_ref() != Some(&self.bound_mode) {
+            match sway.set_binding_mode(&self.bound_mode).await {
+                Ok(()) => active = Some(self.bound_mode.clone()),
+                Err(e) => failures.push(format!("binding mode {:?}: {e}", self.bound_mode)),
+            }
+        }
+        // Bindings are only changed in the active mode, so they are left alone if switching to
+        // the mode they were added to failed
+        if active.as_ref() == Some(&self.bound_mode) {
+            for (key, original) in self.bindings {
+                let restored = match original {
+                    Some(binding) => {
+                        // A binding with other flags is kept next to the session binding
+                        let removed = sway.unbindsym(&key).await;
+                        let bound = sway
+                            .bindsym(&binding.flags, &binding.key, &binding.command)
+                            .await;
+                        removed.and(bound)
+                    }
+                    None => sway.unbindsym(&key).await,
+                };
+                if let Err(e) = restored {
+                    failures.push(format!("binding {key}: {e}"));
+                }
+            }
+        }
+        if let Some(mode) = self.mode.or(current) {
+            if active.as_ref() != Some(&mode) {
+                debug!("Restoring binding mode {mode:?}");
+                if let Err(e) = sway.set_binding_mode(&mode).await {
+                    failures.push(format!("binding mode {mode:?}: {e}"));
+                }
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Failed to restore the key bindings: {}",
+                failures.join("; ")
+            ))
+        }
+    }
+}
+
+/// Returns the variables defined with `set` in the sway config.
+fn parse_variables(sway_config: &str) -> Vec<(String, String)> {
+    let mut variables: Vec<(String, String)> = sway_config
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("set") {
+                return None;
+            }
+            let name = words.next()?.to_string();
+            Some((name, words.collect::<Vec<_>>().join(" ")))
+        })
+        .collect();
+    // Like sway, replace longer names first, so that `$mod` does not replace the start of `$mode`
+    variables.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    variables
+}
+
+/// Replaces the variables in a text.
+fn replace_variables(text: &str, variables: &[(String, String)]) -> String {
+    variables
+        .iter()
+        .fold(text.to_string(), |text, (name, value)| {
+            text.replace(name, value)
+        })
+}
+
+/// Normalizes a key combination for comparing, sway ignores the case and order of the keys.
+fn normalize_key(key: &str) -> Vec<String> {
+    let mut keys: Vec<String> = key.split('+').map(str::to_lowercase).collect();
+    keys.sort();
+    keys
+}
+
+/// Returns the `bindsym` bindings of the sway config with their binding mode. Bindings in
+/// `bindsym { ... }` blocks are included, other blocks like `bar` are skipped.
+fn parse_bindings(sway_config: &str) -> Vec<ConfigBinding> {
+    /// A block of the sway config.
+    enum Block {
+        Mode(String),
+        Bindsym(Vec<String>),
+        Other,
+    }
+
+    let variables = parse_variables(sway_config);
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut bindings = Vec::new();
+    for line in sway_config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "}" {
+            blocks.pop();
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mode = blocks
+            .iter()
+            .rev()
+            .find_map(|block| match block {
+                Block::Mode(mode) => Some(mode.as_str()),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_MODE)
+            .to_string();
+        let binding = match (blocks.last(), words.as_slice()) {
+            (Some(Block::Other), _) => None,
+            (Some(Block::Bindsym(flags)), [key, command @ ..]) => {
+                Some((flags.clone(), key, command))
+            }
+            (_, ["bindsym", rest @ ..]) => {
+                let flags: Vec<String> = rest
+                    .iter()
+                    .take_while(|word| word.starts_with("--"))
+                    .map(|flag| flag.to_string())
+                    .collect();
+                match &rest[flags.len()..] {
+                    ["{"] => {
+                        blocks.push(Block::Bindsym(flags));
+                        continue;
+                    }
+                    [key, command @ ..] => Some((flags, key, command)),
+                    [] => None,
+                }
+            }
+            (_, ["mode", .., "{"]) => {
+                let name = line["mode".len()..line.len() - 1].trim();
+                let name = name.strip_prefix("--pango_markup").unwrap_or(name).trim();
+                let name = replace_variables(name.trim_matches('"'), &variables);
+                blocks.push(Block::Mode(name));
+                continue;
+            }
+            _ => None,
+        };
+        if let Some((flags, key, command)) = binding {
+            bindings.push(ConfigBinding {
+                mode,
+                flags,
+                key: replace_variables(key, &variables),
+                command: command.join(" "),
+            });
+        } else if line.ends_with('{') {
+            blocks.push(Block::Other);
+        }
+    }
+    trace!("Bindings of the sway config: {bindings:?}");
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sway_ipc_interface::fake::{self, FakeSway};
+
+    const CONFIG: &str = "
+set $mod Mod4
+set $mode_system System (l) lock
+bindsym --to-code $mod+Shift+q kill
+bindsym $mod+Return exec foot
+bindsym {
+    $mod+d exec wmenu-run
+}
+mode \"resize\" {
+    bindsym Escape mode default
+}
+mode --pango_markup \"$mode_system\" {
+    bindsym l exec swaylock
+}
+bar {
+    bindsym button4 nop
+}
+";
+
+    #[test]
+    fn test_parse_bindings() {
+        let binding = |mode: &str, flags: &[&str], key: &str, command: &str| ConfigBinding {
+            mode: mode.to_string(),
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            key: key.to_string(),
+            command: command.to_string(),
+        };
+        assert_eq!(
+            parse_bindings(CONFIG),
+            vec![
+                binding("default", &["--to-code"], "Mod4+Shift+q", "kill"),
+                binding("default", &[], "Mod4+Return", "exec foot"),
+                binding("default", &[], "Mod4+d", "exec wmenu-run"),
+                binding("resize", &[], "Escape", "mode default"),
+                binding("System (l) lock", &[], "l", "exec swaylock"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_variables() {
+        let variables = parse_variables(CONFIG);
+        assert_eq!(
+            replace_variables("$mode_system", &variables),
+            "System (l) lock"
+        );
+        assert_eq!(
+            normalize_key(&replace_variables("$mod+shift+Q", &variables)),
+            normalize_key("Shift+Mod4+q")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_fails_halfway() {
+        let (mut sway, commands) = FakeSway::default()
+            .reply(
+                fake::GET_BINDING_MODES,
+                serde_json::json!(["default", "focus"]),
+            )
+            .reply(
+                fake::GET_BINDING_STATE,
+                serde_json::json!({ "name": "default" }),
+            )
+            .reply(fake::GET_CONFIG, serde_json::json!({ "config": CONFIG }))
+            .fail("exec b")
+            .start();
+        let bindings = Bindings::new(
+            Some("focus".to_string()),
+            BTreeMap::from([
+                ("Mod4+a".to_string(), "exec a".to_string()),
+                ("Mod4+b".to_string(), "exec b".to_string()),
+            ]),
+        );
+        assert!(bindings.apply(&mut sway).await.is_err());
+
+        // The binding and the binding mode changed before the failure are restored
+        assert_eq!(
+            commands.lock().unwrap()[3..],
+            ["mode \"focus\"", "unbindsym Mod4+a", "mode \"default\""]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_every_step() {
+        // Without a binding state reply the current binding mode cannot be read
+        let (mut sway, commands) = FakeSway::default().fail("unbindsym Mod4+a").start();
+        let session = BindingsSession {
+            mode: Some("default".to_string()),
+            bound_mode: "focus".to_string(),
+            bindings: vec![("Mod4+a".to_string(), None), ("Mod4+b".to_string(), None)],
+        };
+        assert!(session.restore(&mut sway).await.is_err());
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "mode \"focus\"",
+                "unbindsym Mod4+a",
+                "unbindsym Mod4+b",
+                "mode \"default\""
+            ]
+        );
+    }
+}
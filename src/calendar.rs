@@ -0,0 +1,803 @@
+/// This module reads focus sessions from a local iCalendar file, e.g. one exported by a calendar
+/// sync. Events whose summary matches a pattern, or which have one of the configured categories,
+/// start a session which lasts until the end of the event. The task of the session is the summary
+/// of the event.
+///
+/// The other events are commitments, e.g. meetings. A session which would end too close to the
+/// next commitment is reported, or shortened.
+///
+/// The file is read again whenever it changes, and once a day to expand recurring events into the
+/// occurrences of the next days. Daily and weekly rules are expanded, of other recurring events only
+/// the first occurrence is used. All-day and cancelled events are ignored.
+use crate::cli::SessionArgs;
+use crate::config::{CalendarConfig, OverlapAction};
+use crate::focus;
+use crate::schedule;
+
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use ical::parser::ical::component::IcalEvent;
+use ical::property::Property;
+use ical::IcalParser;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// The format of a date with time in iCalendar.
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// The format of a date in iCalendar.
+const DATE_FORMAT: &str = "%Y%m%d";
+
+/// Days for which the occurrences of recurring events are expanded.
+const LOOK_AHEAD_DAYS: i64 = 14;
+
+/// Time after which the file is read again, to expand recurring events for the next days.
+const EXPAND_INTERVAL: TimeDelta = TimeDelta::hours(24);
+
+/// The days of the week in recurrence rules.
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+/// An event of the calendar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// The summary of the event, used as the task.
+    pub summary: String,
+    /// The start of the event.
+    pub start: DateTime<Utc>,
+    /// The end of the event.
+    pub end: DateTime<Utc>,
+    /// Whether the event starts a focus session, otherwise it is a commitment.
+    pub focus: bool,
+}
+
+impl Event {
+    /// Returns the options of the session for the event. The session ends with the event, its
+    /// duration is set when it starts, which may be after the event started.
+    pub fn args(&self) -> SessionArgs {
+        SessionArgs {
+            task: Some(self.summary.clone()),
+            until: Some(self.end),
+            ..Default::default()
+        }
+    }
+}
+
+/// The time zone of a date with time in iCalendar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EventZone {
+    /// The time is in UTC.
+    Utc,
+    /// A time zone from the tz database, given by `TZID`.
+    Named(Tz),
+    /// The local time zone of the system, for times without time zone.
+    Local,
+}
+
+impl EventZone {
+    /// Converts a wall-clock time in the time zone to UTC.
+    fn resolve(&self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Utc => Some(time.and_utc()),
+            Self::Named(tz) => schedule::resolve(tz, time.date(), time.time()),
+            Self::Local => schedule::resolve(&Local, time.date(), time.time()),
+        }
+    }
+}
+
+/// A daily or weekly recurrence rule, `RRULE`.
+#[derive(Clone, Debug, PartialEq)]
+struct Recurrence {
+    /// Whether the interval is in weeks, otherwise it is in days.
+    weekly: bool,
+    /// Days or weeks between the occurrences.
+    interval: i64,
+    /// Days of the week of the occurrences, empty for every day of a daily rule, or for the day of
+    /// the first occurrence of a weekly rule.
+    days: Vec<Weekday>,
+    /// Number of occurrences.
+    count: Option<usize>,
+    /// The last possible start of an occurrence.
+    until: Option<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// Parses a recurrence rule, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - The value of `RRULE`.
+    /// * `zone` - The time zone of the start of the event, used for `UNTIL` without time zone.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Recurrence`, or an error if the rule is invalid or not supported.
+    fn parse(rule: &str, zone: EventZone) -> Result<Self> {
+        let unsupported = || anyhow!("Unsupported recurrence rule '{rule}'");
+        let mut recurrence = Self {
+            weekly: false,
+            interval: 1,
+            days: Vec::new(),
+            count: None,
+            until: None,
+        };
+        let mut frequency = None;
+        for part in rule.split(';') {
+            let (name, value) = part.split_once('=').ok_or_else(unsupported)?;
+            match name {
+                "FREQ" => frequency = Some(value),
+                "INTERVAL" => {
+                    recurrence.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(unsupported)?
+                }
+                "COUNT" => recurrence.count = Some(value.parse().map_err(|_| unsupported())?),
+                "UNTIL" => {
+                    let until = match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+                        // Occurrences on the last date are included
+                        Ok(date) => {
+                            zone.resolve(date.and_time(NaiveTime::MIN) + TimeDelta::days(1))
+                        }
+                        Err(_) => {
+                            parse_value(value, zone).and_then(|(time, zone)| zone.resolve(time))
+                        }
+                    };
+                    recurrence.until = Some(until.ok_or_else(unsupported)?);
+                }
+                "BYDAY" => {
+                    recurrence.days = value
+                        .split(',')
+                        .map(|day| {
+                            WEEKDAYS
+                                .iter()
+                                .find(|(name, _)| *name == day)
+                                .map(|(_, day)| *day)
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or_else(unsupported)?
+                }
+                // Only changes the occurrences of weekly rules with an interval and several days
+                "WKST" => {}
+                _ => return Err(unsupported()),
+            }
+        }
+        match frequency {
+            Some("DAILY") => {}
+            Some("WEEKLY") => recurrence.weekly = true,
+            _ => return Err(unsupported()),
+        }
+        Ok(recurrence)
+    }
+
+    /// Returns the starts of the occurrences up to a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The start of the first occurrence, as wall-clock time.
+    /// * `zone` - The time zone of the start, which the occurrences keep the wall-clock time in.
+    /// * `to` - The last start to return.
+    fn starts(
+        &self,
+        first: NaiveDateTime,
+        zone: EventZone,
+        to: DateTime<Utc>,
+    ) -> Vec<DateTime<Utc>> {
+        let days = match (self.weekly, self.days.is_empty()) {
+            (true, true) => vec![first.weekday()],
+            _ => self.days.clone(),
+        };
+        let week = |date: NaiveDate| date.week(Weekday::Mon).first_day();
+        let mut starts = Vec::new();
+        for date in first.date().iter_days() {
+            let Some(start) = zone.resolve(date.and_time(first.time())) else {
+                continue;
+            };
+            if start > to
+                || self.until.is_some_and(|until| start > until)
+                || self.count.is_some_and(|count| starts.len() >= count)
+            {
+                break;
+            }
+            let step = if self.weekly {
+                (week(date) - week(first.date())).num_days() / 7
+            } else {
+                (date - first.date()).num_days()
+            };
+            if step % self.interval == 0 && (days.is_empty() || days.contains(&date.weekday())) {
+                starts.push(start);
+            }
+        }
+        starts
+    }
+}
+
+/// A session which would end too close to the next commitment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlap {
+    /// The next commitment.
+    pub event: Event,
+    /// The duration of a session which ends the buffer before the commitment.
+    pub available: Duration,
+    /// Whether the session is shortened to `available`.
+    pub shortened: bool,
+}
+
+impl std::fmt::Display for Overlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let start = self.event.start.with_timezone(&Local).format("%H:%M");
+        let minutes = self.available.as_secs() / 60;
+        if self.shortened {
+            write!(
+                f,
+                "Session shortened to {minutes}m, '{}' starts at {start}",
+                self.event.summary
+            )
+        } else {
+            write!(
+                f,
+                "'{}' starts at {start}, the session should end within {minutes}m",
+                self.event.summary
+            )
+        }
+    }
+}
+
+/// A local iCalendar file with the events which start focus sessions, and the commitments which
+/// sessions have to end before.
+#[derive(Debug)]
+pub struct Calendar {
+    /// Path of the file.
+    file: PathBuf,
+    /// Pattern for the summary of the events.
+    summary: Option<Regex>,
+    /// Categories of the events, in upper case.
+    categories: Vec<String>,
+    /// What to do if a session ends too close to the next commitment.
+    on_overlap: OverlapAction,
+    /// Time between the end of a session and the next commitment.
+    buffer: TimeDelta,
+    /// The events from the last time the file was read.
+    events: Vec<Event>,
+    /// Modification time of the file when it was last read.
+    modified: Option<SystemTime>,
+    /// When the file was last read, recurring events are expanded from then on.
+    read: Option<DateTime<Utc>>,
+}
+
+impl Calendar {
+    /// Creates a `Calendar` from the configuration and reads the file. A file which cannot be
+    /// read only logs a warning, the calendar has no events until it is read successfully.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the calendar.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `Calendar`, or an error if the pattern or the buffer is
+    /// invalid.
+    pub fn from_config(config: &CalendarConfig) -> Result<Self> {
+        let buffer = match &config.buffer {
+            Some(buffer) => focus::parse_duration(buffer)
+                .ok_or_else(|| anyhow!("Invalid calendar buffer '{buffer}'"))?,
+            None => Duration::ZERO,
+        };
+        let file = match config.file.strip_prefix("~/") {
+            Some(path) => dirs::home_dir()
+                .ok_or_else(|| anyhow!("Failed to get home directory"))?
+                .join(path),
+            None => PathBuf::from(&config.file),
+        };
+        let mut calendar = Self {
+            file,
+            summary: config.summary.as_deref().map(Regex::new).transpose()?,
+            categories: config
+                .categories
+                .iter()
+                .flatten()
+                .map(|category| category.to_uppercase())
+                .collect(),
+            on_overlap: config.on_overlap.unwrap_or_default(),
+            buffer: TimeDelta::from_std(buffer)?,
+            events: Vec::new(),
+            modified: None,
+            read: None,
+        };
+        if let Err(e) = calendar.refresh() {
+            warn!("Failed to read calendar {}: {e}", calendar.file.display());
+        }
+        Ok(calendar)
+    }
+
+    /// Reads the file again if it changed since it was last read, or if the occurrences of
+    /// recurring events have to be expanded for the next days.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation. The events from the last
+    /// read are kept on failure.
+    pub fn refresh(&mut self) -> Result<()> {
+        let now = Utc::now();
+        let modified = std::fs::metadata(&self.file)?.modified()?;
+        let expanded = self.read.is_some_and(|read| now - read < EXPAND_INTERVAL);
+        if self.modified == Some(modified) && expanded {
+            return Ok(());
+        }
+        debug!("Reading calendar {}", self.file.display());
+        let file = std::fs::File::open(&self.file)?;
+        self.events = self.parse(std::io::BufReader::new(file), now)?;
+        self.modified = Some(modified);
+        self.read = Some(now);
+        info!("Calendar has {} events", self.events.len());
+        Ok(())
+    }
+
+    /// Returns the focus events which start in a period and have not ended yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - The start of the period, excluded.
+    /// * `now` - The end of the period.
+    pub fn due(&self, after: DateTime<Utc>, now: DateTime<Utc>) -> Vec<Event> {
+        self.events
+            .iter()
+            .filter(|event| event.focus)
+            .filter(|event| event.start > after && event.start <= now && event.end > now)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the first start of a focus event after a time.
+    pub fn next_start(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.events
+            .iter()
+            .filter(|event| event.focus)
+            .map(|event| event.start)
+            .filter(|start| *start > after)
+            .min()
+    }
+
+    /// Checks whether a session ends too close to the next commitment.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the session.
+    /// * `duration` - The duration of the session.
+    ///
+    /// # Returns
+    ///
+    /// The overlap with the next commitment, `None` if there is enough time or overlaps are
+    /// ignored. The session is only shortened if there is time left before the buffer.
+    pub fn overlap(&self, start: DateTime<Utc>, duration: Duration) -> Option<Overlap> {
+        if self.on_overlap == OverlapAction::Ignore {
+            return None;
+        }
+        let end = start + TimeDelta::from_std(duration).ok()?;
+        let event = self
+            .events
+            .iter()
+            .filter(|event| !event.focus && event.start > start && event.start - self.buffer < end)
+            .min_by_key(|event| event.start)?;
+        let available = (event.start - self.buffer - start)
+            .to_std()
+            .unwrap_or_default();
+        Some(Overlap {
+            event: event.clone(),
+            available,
+            shortened: self.on_overlap == OverlapAction::Shorten && !available.is_zero(),
+        })
+    }
+
+    /// Parses the calendar. Recurring events are expanded into the occurrences which have not
+    /// ended at `now` and start within the look-ahead.
+    fn parse(&self, reader: impl BufRead, now: DateTime<Utc>) -> Result<Vec<Event>> {
+        let mut components = Vec::new();
+        for calendar in IcalParser::new(reader) {
+            components.extend(calendar?.events);
+        }
+        // Occurrences which are replaced by a changed copy of the event, with `RECURRENCE-ID`
+        let replaced: Vec<(&str, DateTime<Utc>)> = components
+            .iter()
+            .filter_map(|event| {
+                let id = property(event, "RECURRENCE-ID").and_then(parse_time)?;
+                Some((value(event, "UID")?, id.1.resolve(id.0)?))
+            })
+            .collect();
+
+        let to = now + TimeDelta::days(LOOK_AHEAD_DAYS);
+        let mut events = Vec::new();
+        for component in &components {
+            let Some(event) = self.select(component) else {
+                continue;
+            };
+            let Some(rule) = value(component, "RRULE") else {
+                events.push(event);
+                continue;
+            };
+            let Some((first, zone)) = property(component, "DTSTART").and_then(parse_time) else {
+                continue;
+            };
+            let recurrence = match Recurrence::parse(rule, zone) {
+                Ok(recurrence) => recurrence,
+                Err(e) => {
+                    warn!(
+                        "{e}, only the first occurrence of '{}' is used",
+                        event.summary
+                    );
+                    events.push(event);
+                    continue;
+                }
+            };
+            let uid = value(component, "UID");
+            let excluded: Vec<DateTime<Utc>> = component
+                .properties
+                .iter()
+                .filter(|p| p.name == "EXDATE")
+                .flat_map(parse_times)
+                .chain(
+                    replaced
+                        .iter()
+                        .filter(|(replaced, _)| uid == Some(*replaced))
+                        .map(|(_, start)| *start),
+                )
+                .collect();
+            let duration = event.end - event.start;
+            events.extend(
+                recurrence
+                    .starts(first, zone, to)
+                    .into_iter()
+                    .filter(|start| !excluded.contains(start) && *start + duration > now)
+                    .map(|start| Event {
+                        start,
+                        end: start + duration,
+                        ..event.clone()
+                    }),
+            );
+        }
+        Ok(events)
+    }
+
+    /// Converts an event, which is a focus event if it matches the pattern or the categories. Of a
+    /// recurring event, the first occurrence is returned.
+    fn select(&self, event: &IcalEvent) -> Option<Event> {
+        let summary = value(event, "SUMMARY").unwrap_or_default();
+        let mut categories = event
+            .properties
+            .iter()
+            .filter(|p| p.name == "CATEGORIES")
+            .filter_map(|p| p.value.as_deref())
+            .flat_map(|value| value.split(','))
+            .map(|category| category.trim().to_uppercase());
+        let summary_matches = self
+            .summary
+            .as_ref()
+            .is_some_and(|pattern| pattern.is_match(summary));
+        let focus = summary_matches || categories.any(|c| self.categories.contains(&c));
+        if value(event, "STATUS") == Some("CANCELLED") {
+            return None;
+        }
+
+        let (start, zone) = parse_time(property(event, "DTSTART")?)?;
+        let start = zone.resolve(start)?;
+        let end = match (property(event, "DTEND"), value(event, "DURATION")) {
+            (Some(end), _) => parse_time(end).and_then(|(end, zone)| zone.resolve(end))?,
+            (None, Some(duration)) => start + parse_duration(duration)?,
+            (None, None) => return None,
+        };
+        Some(Event {
+            summary: summary.replace("\\,", ",").replace("\\;", ";"),
+            start,
+            end,
+            focus,
+        })
+    }
+}
+
+/// Returns the first property of an event with a name.
+fn property<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a Property> {
+    event.properties.iter().find(|p| p.name == name)
+}
+
+/// Returns the value of the first property of an event with a name.
+fn value<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a str> {
+    property(event, name).and_then(|p| p.value.as_deref())
+}
+
+/// Returns the time zone of a property, given by `TZID`, or the local time zone.
+fn property_zone(property: &Property) -> EventZone {
+    let tzid = property
+        .params
+        .iter()
+        .flatten()
+        .find(|(name, _)| name == "TZID")
+        .and_then(|(_, values)| values.first());
+    match tzid.map(|tzid| tzid.trim_start_matches('/').parse::<Tz>()) {
+        Some(Ok(tz)) => EventZone::Named(tz),
+        Some(Err(e)) => {
+            warn!("{e}, using the local time zone");
+            EventZone::Local
+        }
+        None => EventZone::Local,
+    }
+}
+
+/// Parses a date with time as wall-clock time, in UTC if it ends with `Z`, otherwise in `zone`.
+fn parse_value(value: &str, zone: EventZone) -> Option<(NaiveDateTime, EventZone)> {
+    match value.strip_suffix('Z') {
+        Some(utc) => Some((
+            NaiveDateTime::parse_from_str(utc, DATE_TIME_FORMAT).ok()?,
+            EventZone::Utc,
+        )),
+        None => Some((
+            NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).ok()?,
+            zone,
+        )),
+    }
+}
+
+/// Parses a date with time as wall-clock time, in UTC, in the time zone given by `TZID`, or in
+/// the local time zone. Dates without time, i.e. all-day events, are not parsed.
+fn parse_time(property: &Property) -> Option<(NaiveDateTime, EventZone)> {
+    parse_value(property.value.as_deref()?, property_zone(property))
+}
+
+/// Parses the comma-separated dates with time of a property, e.g. of `EXDATE`, in UTC. Dates
+/// without time are skipped.
+fn parse_times(property: &Property) -> Vec<DateTime<Utc>> {
+    let zone = property_zone(property);
+    property
+        .value
+        .iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| parse_value(value, zone))
+        .filter_map(|(time, zone)| zone.resolve(time))
+        .collect()
+}
+
+/// Parses a duration, e.g. `PT1H30M` or `P1D`.
+fn parse_duration(value: &str) -> Option<TimeDelta> {
+    static RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^P(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?)?$").unwrap()
+    });
+    let caps = RE.captures(value)?;
+    let part = |index: usize| -> i64 {
+        caps.get(index)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+    Some(
+        TimeDelta::weeks(part(1))
+            + TimeDelta::days(part(2))
+            + TimeDelta::hours(part(3))
+            + TimeDelta::minutes(part(4))
+            + TimeDelta::seconds(part(5)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+SUMMARY:Focus: Write report\r
+DTSTART;TZID=Europe/Berlin:20261019T090000\r
+DTEND;TZID=Europe/Berlin:20261019T105000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Review\\, part 2\r
+CATEGORIES:WORK,Focus\r
+DTSTART:20261019T130000Z\r
+DURATION:PT45M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Focus block\r
+STATUS:CANCELLED\r
+DTSTART:20261019T150000Z\r
+DTEND:20261019T160000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Focus day\r
+DTSTART;VALUE=DATE:20261020\r
+DTEND;VALUE=DATE:20261021\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Lunch\r
+DTSTART:20261019T110000Z\r
+DTEND:20261019T120000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn calendar(on_overlap: OverlapAction) -> Calendar {
+        let mut calendar = Calendar {
+            file: PathBuf::new(),
+            summary: Some(Regex::new("^Focus").unwrap()),
+            categories: vec!["FOCUS".to_string()],
+            on_overlap,
+            buffer: TimeDelta::minutes(5),
+            events: Vec::new(),
+            modified: None,
+            read: None,
+        };
+        calendar.events = calendar
+            .parse(CALENDAR.as_bytes(), utc("2026-10-19T06:00:00Z"))
+            .unwrap();
+        calendar
+    }
+
+    #[test]
+    fn test_parse() {
+        let events = calendar(OverlapAction::Warn).events;
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    summary: "Focus: Write report".to_string(),
+                    start: utc("2026-10-19T07:00:00Z"),
+                    end: utc("2026-10-19T08:50:00Z"),
+                    focus: true,
+                },
+                Event {
+                    summary: "Review, part 2".to_string(),
+                    start: utc("2026-10-19T13:00:00Z"),
+                    end: utc("2026-10-19T13:45:00Z"),
+                    focus: true,
+                },
+                Event {
+                    summary: "Lunch".to_string(),
+                    start: utc("2026-10-19T11:00:00Z"),
+                    end: utc("2026-10-19T12:00:00Z"),
+                    focus: false,
+                },
+            ]
+        );
+
+        // A session started late still ends with the event
+        let args = events[0].args();
+        assert_eq!(args.duration, None);
+        assert_eq!(args.until, Some(utc("2026-10-19T08:50:00Z")));
+        assert_eq!(args.task.as_deref(), Some("Focus: Write report"));
+    }
+
+    #[test]
+    fn test_recurring() {
+        const RECURRING: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART;TZID=Europe/Berlin:20261014T093000\r
+DTEND;TZID=Europe/Berlin:20261014T094500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20261028T083000Z\r
+EXDATE;TZID=Europe/Berlin:20261026T093000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID;TZID=Europe/Berlin:20261021T093000\r
+SUMMARY:Standup\r
+DTSTART;TZID=Europe/Berlin:20261021T110000\r
+DTEND;TZID=Europe/Berlin:20261021T111500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Focus\r
+DTSTART:20261001T080000Z\r
+DURATION:PT2H\r
+RRULE:FREQ=DAILY;INTERVAL=2;COUNT=10\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Focus monthly\r
+DTSTART:20260901T080000Z\r
+DURATION:PT1H\r
+RRULE:FREQ=MONTHLY;BYMONTHDAY=1\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = calendar(OverlapAction::Warn)
+            .parse(RECURRING.as_bytes(), utc("2026-10-19T07:00:00Z"))
+            .unwrap();
+        let starts = |summary: &str| -> Vec<DateTime<Utc>> {
+            let mut starts: Vec<_> = events
+                .iter()
+                .filter(|event| event.summary == summary)
+                .map(|event| event.start)
+                .collect();
+            starts.sort();
+            starts
+        };
+        // The replaced and the excluded occurrences are skipped, the wall-clock time is kept after
+        // the end of daylight saving time
+        assert_eq!(
+            starts("Standup"),
+            vec![
+                utc("2026-10-19T07:30:00Z"),
+                utc("2026-10-21T09:00:00Z"),
+                utc("2026-10-28T08:30:00Z"),
+            ]
+        );
+        // Occurrences which ended before now, or after the count, are dropped
+        assert_eq!(starts("Focus"), vec![utc("2026-10-19T08:00:00Z")]);
+        // Unsupported rules only use the first occurrence
+        assert_eq!(starts("Focus monthly"), vec![utc("2026-09-01T08:00:00Z")]);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let fifty_minutes = Duration::from_secs(50 * 60);
+        let warn = calendar(OverlapAction::Warn);
+        assert_eq!(
+            warn.overlap(utc("2026-10-19T10:00:00Z"), fifty_minutes),
+            None
+        );
+        let overlap = warn
+            .overlap(utc("2026-10-19T10:20:00Z"), fifty_minutes)
+            .unwrap();
+        assert_eq!(overlap.event.summary, "Lunch");
+        assert_eq!(overlap.available, Duration::from_secs(35 * 60));
+        assert!(!overlap.shortened);
+        // Focus events are no commitments
+        assert_eq!(
+            warn.overlap(utc("2026-10-19T12:30:00Z"), fifty_minutes),
+            None
+        );
+
+        let shorten = calendar(OverlapAction::Shorten);
+        let overlap = shorten.overlap(utc("2026-10-19T10:20:00Z"), fifty_minutes);
+        assert!(overlap.unwrap().shortened);
+        // Without time left, the session is not shortened to nothing
+        let overlap = shorten.overlap(utc("2026-10-19T10:58:00Z"), fifty_minutes);
+        assert!(!overlap.unwrap().shortened);
+        assert_eq!(
+            calendar(OverlapAction::Ignore).overlap(utc("2026-10-19T10:20:00Z"), fifty_minutes),
+            None
+        );
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let config = CalendarConfig {
+            file: "/nonexistent/focus-time/calendar.ics".to_string(),
+            summary: None,
+            categories: None,
+            on_overlap: None,
+            buffer: None,
+        };
+        let calendar = Calendar::from_config(&config).unwrap();
+        assert!(calendar.events.is_empty());
+        assert_eq!(
+            calendar.overlap(utc("2026-10-19T10:20:00Z"), Duration::from_secs(3000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT45M"), Some(TimeDelta::minutes(45)));
+        assert_eq!(
+            parse_duration("P1DT1H30S"),
+            Some(TimeDelta::days(1) + TimeDelta::hours(1) + TimeDelta::seconds(30))
+        );
+        assert_eq!(parse_duration("45M"), None);
+    }
+}
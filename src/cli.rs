@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::builder::PossibleValuesParser;
+use clap::{Args, Command, Parser, Subcommand};
 
 /// Command line interface for the wait command
 #[derive(Parser)]
@@ -10,6 +12,33 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
 
+    #[command(flatten)]
+    pub session: SessionArgs,
+
+    /// Path to the configuration file
+    #[arg(short = 'c', long)]
+    pub config: Option<String>,
+
+    /// Log level (e.g. "trace", "debug", "info", "warn", "error")
+    #[arg(short, long, default_value = "error")]
+    pub log_level: String,
+
+    /// Stop the running focus-time and take over
+    #[arg(long, conflicts_with = "queue")]
+    pub replace: bool,
+
+    /// Run this session after the one of the running focus-time
+    #[arg(long)]
+    pub queue: bool,
+
+    /// Run or control a named timer next to the focus session, e.g. "oven"
+    #[arg(long, global = true)]
+    pub name: Option<String>,
+}
+
+/// Options of a single focus session
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct SessionArgs {
     /// Duration to wait (e.g. "5s", "2m", "1h")
     pub duration: Option<String>,
 
@@ -25,23 +54,79 @@ pub struct Cli {
     #[arg(short = 'p', long)]
     pub print_time: bool,
 
-    /// Path to the configuration file
-    #[arg(short = 'c', long)]
-    pub config: Option<String>,
+    /// Replace the status bar contents with the remaining time instead of hiding the bar
+    #[arg(short = 'b', long)]
+    pub focus_bar: bool,
 
-    /// Log level (e.g. "trace", "debug", "info", "warn", "error")
-    #[arg(short, long, default_value = "error")]
-    pub log_level: String,
+    /// Task to work on during the focus session
+    #[arg(short = 't', long)]
+    pub task: Option<String>,
+
+    /// Switch to this workspace during the focus session
+    #[arg(short = 'w', long)]
+    pub workspace: Option<String>,
+
+    /// Move the focused window along to the focus workspace
+    #[arg(short = 'm', long)]
+    pub move_window: bool,
+
+    /// Profile of the config file to use, e.g. "deep"
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// End of the session, which sets the duration when the session starts, e.g. the end of a
+    /// calendar event
+    #[arg(skip)]
+    pub until: Option<DateTime<Utc>>,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
+    /// Ask the daemon to start a focus session, or queue it after the running one
+    Start {
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Run in the background and start focus sessions on request
+    Daemon,
+    /// Start a focus session at a time of the day, or list and cancel scheduled sessions
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Schedule {
+        #[command(subcommand)]
+        command: Option<ScheduleCommands>,
+
+        /// Time of the day to start the session at (e.g. "09:00")
+        #[arg(required = true)]
+        time: Option<String>,
+
+        #[command(flatten)]
+        session: SessionArgs,
+    },
     /// Stop the timer
     Stop,
     /// Pause/Resume the timer
     TogglePause,
+    /// List the focus timer and the named timers with their status
+    List,
     /// Get the status of the timer
-    Status,
+    Status {
+        /// Also print the phase of the session and its history
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Print the status of the timer continuously in the i3bar protocol
+    #[command(hide = true)]
+    Bar,
+    /// Restore the desktop after a session that did not clean up
+    Recover,
+    /// Install the files to start the daemon on demand with D-Bus and systemd
+    InstallService {
+        /// Only check whether the installed files are up to date
+        #[arg(long)]
+        check: bool,
+    },
+    /// Remove the files installed by install-service
+    UninstallService,
     /// Generate shell completions
     #[command(hide = true)]
     Completions {
@@ -50,3 +135,41 @@ pub enum Commands {
         shell: clap_complete::Shell,
     },
 }
+
+#[derive(Subcommand, Debug)]
+pub enum ScheduleCommands {
+    /// List the scheduled sessions
+    List,
+    /// Cancel a session scheduled with `focus-time schedule`
+    Cancel {
+        /// The id of the session, as shown by `focus-time schedule list`
+        id: u32,
+    },
+}
+
+/// Adds the names of the profiles as the values of `--profile`, so that they are completed by
+/// the shell. The names are part of the generated completion script, which has to be generated
+/// again after a profile was added.
+///
+/// # Arguments
+///
+/// * `cmd` - The command, its subcommands are changed as well.
+/// * `profiles` - The names of the profiles in the config file.
+///
+/// # Returns
+///
+/// The changed command.
+pub fn with_profile_names(mut cmd: Command, profiles: &[String]) -> Command {
+    if !profiles.is_empty() && cmd.get_arguments().any(|arg| arg.get_id() == "profile") {
+        let values = PossibleValuesParser::new(profiles.iter().cloned());
+        cmd = cmd.mut_arg("profile", |arg| arg.value_parser(values));
+    }
+    let names: Vec<String> = cmd
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    for name in names {
+        cmd = cmd.mut_subcommand(name, |subcommand| with_profile_names(subcommand, profiles));
+    }
+    cmd
+}
@@ -1,5 +1,10 @@
-use crate::cli::Commands;
+use crate::cli::{Commands, ScheduleCommands, SessionArgs};
+use crate::daemon;
 use anyhow::Result;
+use chrono::{Local, TimeZone};
+use std::collections::HashMap;
+use zbus::fdo::DBusProxy;
+use zbus::zvariant::Value;
 use zbus::Connection;
 
 use zbus::proxy;
@@ -12,15 +17,84 @@ use zbus::proxy;
 pub trait FocusTimer {
     fn get_remaining_time(&self) -> zbus::Result<String>;
     fn get_paused(&self) -> zbus::Result<bool>;
+    fn get_interruptions(&self) -> zbus::Result<u32>;
+    fn get_task(&self) -> zbus::Result<String>;
+    fn get_state(&self) -> zbus::Result<String>;
+    fn get_transitions(&self) -> zbus::Result<Vec<(u64, String)>>;
+    fn get_queue_length(&self) -> zbus::Result<u32>;
+    fn start(&self, duration: &str, options: HashMap<&str, Value<'_>>) -> zbus::Result<u32>;
+    fn schedule(
+        &self,
+        time: &str,
+        duration: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<(u32, u64)>;
+    fn get_schedule(&self) -> zbus::Result<Vec<(u32, u64, bool, String)>>;
+    fn cancel_scheduled(&self, id: u32) -> zbus::Result<()>;
     fn stop_timer(&self) -> zbus::Result<()>;
     fn toggle_pause(&self) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn state_changed(&self, state: String) -> zbus::Result<()>;
 }
 
-pub async fn handle_command(command: Commands) -> Result<()> {
+/// Creates a proxy of the focus timer, or of a named timer.
+///
+/// # Arguments
+///
+/// * `connection` - The connection to the session bus.
+/// * `name` - The name of the timer, `None` for the focus timer.
+///
+/// # Returns
+///
+/// A `Result` containing the proxy.
+pub async fn proxy<'a>(connection: &Connection, name: Option<&str>) -> Result<FocusTimerProxy<'a>> {
+    Ok(FocusTimerProxy::builder(connection)
+        .destination(daemon::bus_name(name)?)?
+        .build()
+        .await?)
+}
+
+pub async fn handle_command(command: Commands, name: Option<String>) -> Result<()> {
     let connection = Connection::session().await?;
-    let proxy = FocusTimerProxy::new(&connection).await?;
+    if let Commands::List = command {
+        return list(&connection).await;
+    }
+    let proxy = proxy(&connection, name.as_deref()).await?;
 
     match command {
+        Commands::Start { session } => start(&proxy, &session).await?,
+        Commands::Schedule {
+            command: Some(ScheduleCommands::List),
+            ..
+        } => {
+            let schedule = proxy.get_schedule().await?;
+            if schedule.is_empty() {
+                println!("No sessions are scheduled.");
+            }
+            for (id, next, from_config, description) in schedule {
+                let source = if from_config { " (configured)" } else { "" };
+                println!("{id:>3}  {}  {description}{source}", format_time(next));
+            }
+        }
+        Commands::Schedule {
+            command: Some(ScheduleCommands::Cancel { id }),
+            ..
+        } => {
+            proxy.cancel_scheduled(id).await?;
+            println!("Scheduled session {id} cancelled.");
+        }
+        Commands::Schedule {
+            command: None,
+            time,
+            session,
+        } => {
+            let duration = session.duration.as_deref().unwrap_or_default();
+            let time = time.unwrap_or_default();
+            let (id, start) = proxy
+                .schedule(&time, duration, daemon::to_options(&session))
+                .await?;
+            println!("Focus session {id} scheduled for {}.", format_time(start));
+        }
         Commands::Stop => {
             proxy.stop_timer().await?;
             println!("Focus timer stopped.");
@@ -29,19 +103,166 @@ pub async fn handle_command(command: Commands) -> Result<()> {
             proxy.toggle_pause().await?;
             println!("Focus timer toggled pause.");
         }
-        Commands::Status => {
-            let time = proxy.get_remaining_time().await?;
-            let paused = proxy.get_paused().await?;
-            if paused {
-                println!("{time} (paused)");
-            } else {
-                println!("{time}");
+        Commands::Status { verbose } => {
+            println!("{}", status_text(&proxy).await?);
+            let queued = proxy.get_queue_length().await?;
+            if queued > 0 {
+                println!("Queued sessions: {queued}");
+            }
+            if verbose {
+                println!("State: {}", proxy.get_state().await?);
+                println!("History:");
+                for line in history_lines(&proxy.get_transitions().await?) {
+                    println!("  {line}");
+                }
             }
         }
-        Commands::Completions { .. } => {
+        Commands::List
+        | Commands::Daemon
+        | Commands::Bar
+        | Commands::Recover
+        | Commands::InstallService { .. }
+        | Commands::UninstallService
+        | Commands::Completions { .. } => {
             // This is handled in main.rs and should not be reached here.
         }
     }
 
     Ok(())
 }
+
+/// Formats a time from D-Bus in the local time zone, e.g. `Mon 19 Oct 09:00`.
+///
+/// # Arguments
+///
+/// * `millis` - The time in milliseconds since the Unix epoch.
+fn format_time(millis: u64) -> String {
+    match Local.timestamp_millis_opt(millis as i64).single() {
+        Some(time) => time.format("%a %d %b %H:%M").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Prints the status of the focus timer and of every named timer.
+///
+/// # Arguments
+///
+/// * `connection` - The connection to the session bus.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+async fn list(connection: &Connection) -> Result<()> {
+    let dbus = DBusProxy::new(connection).await?;
+    let mut names = dbus.list_names().await?;
+    names.sort();
+    let mut found = false;
+    for bus_name in &names {
+        let Some(name) = daemon::timer_name(bus_name) else {
+            continue;
+        };
+        let proxy = proxy(connection, name).await?;
+        let status = status_text(&proxy).await?;
+        println!("{}: {status}", name.unwrap_or("focus"));
+        found = true;
+    }
+    if !found {
+        println!("No timer is running.");
+    }
+    Ok(())
+}
+
+/// Asks the running focus timer to start a session, or to queue it after the running one.
+///
+/// # Arguments
+///
+/// * `proxy` - The proxy of the running focus timer.
+/// * `session` - The options of the session.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub async fn start(proxy: &FocusTimerProxy<'_>, session: &SessionArgs) -> Result<()> {
+    let duration = session.duration.as_deref().unwrap_or_default();
+    match proxy.start(duration, daemon::to_options(session)).await? {
+        0 => println!("Focus session started."),
+        position => println!("Focus session queued, {position} session(s) before it."),
+    }
+    Ok(())
+}
+
+/// Queries the running focus timer and formats its status as a single line.
+///
+/// # Arguments
+///
+/// * `proxy` - The proxy of the running focus timer.
+///
+/// # Returns
+///
+/// A `Result` containing the status, e.g. `00:14:32 (paused) - Write report`, or `idle` if the
+/// daemon is not running a session.
+pub async fn status_text(proxy: &FocusTimerProxy<'_>) -> zbus::Result<String> {
+    if proxy.get_state().await? == "idle" {
+        return Ok("idle".to_string());
+    }
+    let mut status = proxy.get_remaining_time().await?;
+    if proxy.get_paused().await? {
+        status.push_str(" (paused)");
+    }
+    let interruptions = proxy.get_interruptions().await?;
+    if interruptions > 0 {
+        status.push_str(&format!(" [{interruptions} interruptions]"));
+    }
+    let task = proxy.get_task().await?;
+    if !task.is_empty() {
+        status.push_str(&format!(" - {task}"));
+    }
+    Ok(status)
+}
+
+/// Formats the phase changes of a session, with the time since the start of the session.
+///
+/// # Arguments
+///
+/// * `transitions` - The phase changes, with the time in milliseconds since the Unix epoch.
+///
+/// # Returns
+///
+/// One line per change, e.g. `00:25:00 finished (completed)`.
+fn history_lines(transitions: &[(u64, String)]) -> Vec<String> {
+    let start = transitions.first().map_or(0, |(time, _)| *time);
+    transitions
+        .iter()
+        .map(|(time, phase)| {
+            let secs = time.saturating_sub(start) / 1000;
+            format!(
+                "{:02}:{:02}:{:02} {phase}",
+                secs / 3600,
+                (secs / 60) % 60,
+                secs % 60
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_lines() {
+        let transitions = vec![
+            (1_000_000, "starting".to_string()),
+            (1_001_500, "running".to_string()),
+            (4_601_500, "finished (stopped)".to_string()),
+        ];
+        assert_eq!(
+            history_lines(&transitions),
+            vec![
+                "00:00:00 starting",
+                "00:00:01 running",
+                "01:00:01 finished (stopped)"
+            ]
+        );
+    }
+}
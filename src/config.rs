@@ -3,15 +3,52 @@
 /// `XDG_CONFIG_HOME/focus-time/config.toml`. Another file can be specified as an argument when
 /// running the program.
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use log::{debug, trace};
 
 /// Configuration for the focus timer.
+///
+/// Unknown keys are collected instead of rejected by serde, which does not support
+/// `deny_unknown_fields` together with `flatten`, and reported by [`ConfigFile::check_unknown`].
 #[derive(Deserialize, Default, Debug)]
 #[serde(rename_all = "kebab-case")]
-#[serde(deny_unknown_fields)]
 pub struct ConfigFile {
+    /// Options of every session, which profiles can override.
+    #[serde(flatten)]
+    pub session: SessionConfig,
+    /// Sessions which the daemon starts at fixed times.
+    pub schedule: Option<Vec<ScheduleConfig>>,
+    /// Calendar file with events which start sessions.
+    pub calendar: Option<CalendarConfig>,
+    /// Profile which is used if none is selected.
+    pub default_profile: Option<String>,
+    /// Named sets of session options, which override the options above.
+    pub profile: Option<BTreeMap<String, ProfileConfig>>,
+    /// Keys which are not known. Has to stay the last field, so that it only gets the keys which
+    /// none of the other fields took.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// A named set of session options, selected with `--profile`. Options which are not set are
+/// taken from the top level of the config file.
+#[derive(Deserialize, Default, Debug)]
+pub struct ProfileConfig {
+    /// The options of the profile.
+    #[serde(flatten)]
+    pub session: SessionConfig,
+    /// Keys which are not known, reported by [`ConfigFile::check_unknown`].
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// The options of a session, which can be set at the top level of the config file and in
+/// profiles.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct SessionConfig {
     /// Duration of the focus timer.
     pub duration: Option<String>,
     /// Whether to disable notifications.
@@ -20,6 +57,455 @@ pub struct ConfigFile {
     pub keep_status_bar: Option<bool>,
     /// Whether to print the remaining time.
     pub print_time: Option<bool>,
+    /// Whether to replace the status bar contents instead of hiding the bar.
+    pub focus_bar: Option<bool>,
+    /// Name of the workspace to switch to during the focus session.
+    pub workspace: Option<String>,
+    /// Whether to move the focused window to the focus workspace.
+    pub move_window: Option<bool>,
+    /// External status bars, e.g. Waybar, which are hidden together with the sway bars.
+    pub bar: Option<Vec<ExternalBarConfig>>,
+    /// Windows which are moved out of sight during the focus session.
+    pub stash: Option<StashConfig>,
+    /// Reaction to focusing a blocked window during the focus session.
+    pub guard: Option<GuardConfig>,
+    /// Settings applied to sway during the focus session.
+    pub sway: Option<SwayConfig>,
+    /// Outputs which are turned off during the focus session.
+    pub outputs: Option<OutputsConfig>,
+    /// D-Bus methods to call on session events.
+    pub dbus_actions: Option<Vec<DbusActionConfig>>,
+    /// Media players controlled over MPRIS during the focus session.
+    pub media: Option<MediaConfig>,
+    /// Processes which are stopped during the focus session.
+    pub processes: Option<ProcessesConfig>,
+    /// Operations which are inhibited during the focus session, e.g. `idle` and `sleep`.
+    pub inhibit: Option<Vec<InhibitWhat>>,
+    /// Pausing the timer while the user is away.
+    pub auto_pause: Option<AutoPauseConfig>,
+}
+
+impl SessionConfig {
+    /// Merges two sets of options, the options set here replace the ones of `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            duration: self.duration.or(other.duration),
+            no_notification: self.no_notification.or(other.no_notification),
+            keep_status_bar: self.keep_status_bar.or(other.keep_status_bar),
+            print_time: self.print_time.or(other.print_time),
+            focus_bar: self.focus_bar.or(other.focus_bar),
+            workspace: self.workspace.or(other.workspace),
+            move_window: self.move_window.or(other.move_window),
+            bar: self.bar.or(other.bar),
+            stash: self.stash.or(other.stash),
+            guard: self.guard.or(other.guard),
+            sway: self.sway.or(other.sway),
+            outputs: self.outputs.or(other.outputs),
+            dbus_actions: self.dbus_actions.or(other.dbus_actions),
+            media: self.media.or(other.media),
+            processes: self.processes.or(other.processes),
+            inhibit: self.inhibit.or(other.inhibit),
+            auto_pause: self.auto_pause.or(other.auto_pause),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Returns the names of the profiles.
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profile
+            .iter()
+            .flat_map(|profiles| profiles.keys().cloned())
+            .collect()
+    }
+
+    /// Checks that the config file has no unknown keys at the top level or in a profile.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an error naming the first unknown key.
+    pub fn check_unknown(&self) -> anyhow::Result<()> {
+        let profiles = self
+            .profile
+            .iter()
+            .flatten()
+            .map(|(name, profile)| (format!("profile.{name}."), &profile.unknown));
+        for (prefix, unknown) in std::iter::once((String::new(), &self.unknown)).chain(profiles) {
+            if let Some(key) = unknown.keys().next() {
+                return Err(anyhow::anyhow!("Unknown config key `{prefix}{key}`"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a profile exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the profile.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an error if there is no profile with the name.
+    pub fn check_profile(&self, name: &str) -> anyhow::Result<()> {
+        match &self.profile {
+            Some(profiles) if profiles.contains_key(name) => Ok(()),
+            _ => Err(anyhow::anyhow!("Unknown profile '{name}'")),
+        }
+    }
+
+    /// Applies a profile, the options it sets replace the ones at the top level.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the profile, `default-profile` if not set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config with the profile applied, or an error if the profile does
+    /// not exist.
+    pub fn with_profile(mut self, name: Option<&str>) -> anyhow::Result<Self> {
+        let Some(name) = name.or(self.default_profile.as_deref()).map(str::to_string) else {
+            return Ok(self);
+        };
+        self.check_profile(&name)?;
+        debug!("Using profile `{name}`");
+        let profile = self
+            .profile
+            .as_mut()
+            .and_then(|profiles| profiles.remove(&name))
+            .unwrap_or_default();
+        self.session = profile.session.or(self.session);
+        Ok(self)
+    }
+}
+
+/// Configuration for starting sessions from the events of a local iCalendar file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// Path of the `.ics` file.
+    pub file: String,
+    /// Regular expression for the summary of the events, e.g. `Focus`.
+    pub summary: Option<String>,
+    /// Categories of the events, e.g. `["FOCUS"]`, compared ignoring case.
+    pub categories: Option<Vec<String>>,
+    /// What to do if a session ends too close to the next commitment.
+    pub on_overlap: Option<OverlapAction>,
+    /// Time between the end of a session and the next commitment, e.g. `5m`.
+    pub buffer: Option<String>,
+}
+
+/// What to do if a session ends too close to the next event of the calendar.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlapAction {
+    /// Show a warning when the session starts and in the notification at the end.
+    #[default]
+    Warn,
+    /// Shorten the session so that it ends in time.
+    Shorten,
+    /// Do not check the next event.
+    Ignore,
+}
+
+/// Configuration for a session which is started at a fixed time of the day.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Days of the week, e.g. `["mon", "tue"]`, every day if not set.
+    pub days: Option<Vec<String>>,
+    /// Time of the day, e.g. `09:00`.
+    pub time: String,
+    /// Time zone of the time, e.g. `Europe/Berlin`, the local time zone if not set.
+    pub time_zone: Option<String>,
+    /// Duration of the session, the configured duration if not set.
+    pub duration: Option<String>,
+    /// Task to work on during the session.
+    pub task: Option<String>,
+    /// Workspace to switch to during the session.
+    pub workspace: Option<String>,
+    /// Profile of the session, `default-profile` if not set.
+    pub profile: Option<String>,
+}
+
+/// Configuration for pausing the timer when the screen is locked or the user is idle.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct AutoPauseConfig {
+    /// Whether to pause when the screen is locked, enabled by default.
+    pub on_lock: Option<bool>,
+    /// Whether to pause when the user is idle, enabled by default.
+    pub on_idle: Option<bool>,
+    /// What to do when the user returns.
+    pub on_return: Option<ReturnAction>,
+    /// How long the user has to be away before the timer is paused, e.g. `2m`.
+    pub threshold: Option<String>,
+}
+
+/// What to do when the user returns after the timer was paused automatically.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReturnAction {
+    /// Resume the timer.
+    #[default]
+    Resume,
+    /// Keep the timer paused until it is resumed manually.
+    StayPaused,
+}
+
+/// An operation which can be inhibited through logind.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum InhibitWhat {
+    /// The system going idle, which usually locks the screen.
+    Idle,
+    /// Suspending or hibernating the system.
+    Sleep,
+    /// Powering off or rebooting the system.
+    Shutdown,
+}
+
+impl InhibitWhat {
+    /// Returns the name logind uses for the operation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Sleep => "sleep",
+            Self::Shutdown => "shutdown",
+        }
+    }
+}
+
+/// Configuration of the media players controlled during the focus session.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct MediaConfig {
+    /// Player to start at the beginning of the session, e.g. `spotify`.
+    pub player: Option<String>,
+    /// URI to open in the player, e.g. a focus playlist.
+    pub uri: Option<String>,
+    /// Whether to pause playing media while the timer is paused, enabled by default.
+    pub pause_on_break: Option<bool>,
+    /// Whether to pause playing media at the end of the session, enabled by default.
+    pub pause_on_end: Option<bool>,
+}
+
+/// An event in the lifecycle of a focus session.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionEvent {
+    /// The session started.
+    Start,
+    /// The session ended.
+    End,
+    /// The timer was paused.
+    Pause,
+    /// The timer was resumed.
+    Resume,
+}
+
+/// The message bus to send a D-Bus method call to.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BusType {
+    /// The session bus of the user.
+    #[default]
+    Session,
+    /// The system bus.
+    System,
+}
+
+/// A D-Bus method call, which is made when a session event occurs.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct DbusActionConfig {
+    /// The session event to make the call on.
+    pub event: SessionEvent,
+    /// The bus to use, the session bus by default.
+    pub bus: Option<BusType>,
+    /// The bus name of the service, e.g. `org.mpris.MediaPlayer2.spotify`.
+    pub destination: String,
+    /// The object path, e.g. `/org/mpris/MediaPlayer2`.
+    pub path: String,
+    /// The interface, e.g. `org.mpris.MediaPlayer2.Player`.
+    pub interface: String,
+    /// The method name, e.g. `Pause`.
+    pub method: String,
+    /// The D-Bus signature of the arguments, e.g. `su`.
+    pub signature: Option<String>,
+    /// The arguments, matching the signature.
+    #[serde(default)]
+    pub args: Vec<toml::Value>,
+}
+
+/// Configuration of the outputs to turn off during the focus session.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct OutputsConfig {
+    /// Outputs to power off, they keep their workspaces.
+    #[serde(default)]
+    pub power_off: Vec<String>,
+    /// Outputs to disable, their workspaces move to the remaining outputs.
+    #[serde(default)]
+    pub disable: Vec<String>,
+}
+
+/// Configuration of the processes to stop during the focus session.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ProcessesConfig {
+    /// Processes to stop with `SIGSTOP`, they are continued at the end of the session.
+    #[serde(default)]
+    pub stop: Vec<ProcessRuleConfig>,
+}
+
+/// A process, selected by its name or its command line.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ProcessRuleConfig {
+    /// The process name, e.g. `slack`.
+    Name(String),
+    /// A regular expression for the command line.
+    Cmdline(CmdlineRuleConfig),
+}
+
+/// A regular expression for the command line of a process.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CmdlineRuleConfig {
+    /// Regular expression for the arguments, joined with spaces.
+    pub cmdline: String,
+}
+
+/// Configuration of sway settings for the focus session.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct SwayConfig {
+    /// Visual cues that a focus session is running.
+    pub visual: Option<VisualConfig>,
+    /// Binding mode to switch to during the focus session.
+    pub mode: Option<String>,
+    /// Key bindings which only exist during the focus session, mapping keys to sway commands.
+    pub bindings: Option<BTreeMap<String, String>>,
+    /// Sway commands to run at the start of the focus session.
+    pub on_start: Option<Vec<SwayCommandConfig>>,
+    /// Sway commands to run at the end of the focus session. Defaults to the `undo` commands of
+    /// `on-start` in reverse order.
+    pub on_end: Option<Vec<String>>,
+}
+
+/// A sway command, optionally with the command that undoes it.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum SwayCommandConfig {
+    /// A plain command.
+    Command(String),
+    /// A command with its inverse.
+    WithUndo(SwayCommandPair),
+}
+
+/// A sway command together with the command that undoes it.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SwayCommandPair {
+    /// The command to run at the start of the session.
+    pub command: String,
+    /// The command to run at the end of the session.
+    pub undo: String,
+}
+
+/// Visual changes applied during the focus session. Unset values are left unchanged.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct VisualConfig {
+    /// Border color of the focused window, e.g. `#d79921`.
+    pub focused_border: Option<String>,
+    /// Inner gaps between windows in pixels.
+    pub gaps_inner: Option<i32>,
+    /// Outer gaps around the workspace in pixels.
+    pub gaps_outer: Option<i32>,
+    /// Opacity of all windows that do not have focus, between 0.0 and 1.0.
+    pub unfocused_opacity: Option<f64>,
+    /// Whether to make the focused window fullscreen.
+    pub fullscreen: Option<bool>,
+}
+
+/// Configuration for stashing distracting windows.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct StashConfig {
+    /// Where to move the windows, either `scratchpad` or the name of a workspace.
+    pub target: Option<String>,
+    /// Rules selecting the windows to stash.
+    #[serde(default)]
+    pub rule: Vec<WindowRuleConfig>,
+}
+
+/// Configuration for the distraction guard.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct GuardConfig {
+    /// What to do when a blocked window gets focus.
+    pub action: Option<GuardAction>,
+    /// Rules selecting the blocked windows.
+    #[serde(default)]
+    pub rule: Vec<WindowRuleConfig>,
+}
+
+/// Reaction to focusing a blocked window. Every case is counted as an interruption.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuardAction {
+    /// Only record the interruption.
+    #[default]
+    Record,
+    /// Send a notification.
+    Notify,
+    /// Move the focus back to the previously focused window.
+    Refocus,
+}
+
+/// Properties to match a window by. Each value is a regular expression.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct WindowRuleConfig {
+    /// Regular expression for the Wayland `app_id`.
+    pub app_id: Option<String>,
+    /// Regular expression for the X11 window class.
+    pub class: Option<String>,
+    /// Regular expression for the window title.
+    pub title: Option<String>,
+}
+
+/// Configuration of an external status bar process.
+///
+/// The bar is either toggled by sending a signal to the process, or hidden and shown with the
+/// configured commands.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ExternalBarConfig {
+    /// Name of the bar process, e.g. `waybar`.
+    pub process: Option<String>,
+    /// Signal which toggles the visibility of the bar, e.g. `SIGUSR1`.
+    pub signal: Option<String>,
+    /// Shell command to hide the bar.
+    pub hide_command: Option<String>,
+    /// Shell command to show the bar.
+    pub show_command: Option<String>,
 }
 
 /// Loads the configuration from a file.
@@ -59,11 +545,67 @@ pub fn load_from_file(cli_config: &Option<String>) -> anyhow::Result<ConfigFile>
         let config_content = std::fs::read_to_string(&config_path)?;
         trace!("Parsing: {config_content:?}");
 
-        let config = toml::from_str(&config_content)?;
+        let config: ConfigFile = toml::from_str(&config_content)?;
         trace!("Parsed config: {config:?}");
+        config.check_unknown()?;
         Ok(config)
     } else {
         debug!("Config file not found. Using default config.");
         Ok(ConfigFile::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_profile() {
+        let config = || -> ConfigFile {
+            toml::from_str(
+                r#"
+                duration = "25m"
+                workspace = "focus"
+                default-profile = "deep"
+
+                [profile.deep]
+                duration = "90m"
+                keep-status-bar = true
+
+                [profile.review]
+                workspace = "review"
+                "#,
+            )
+            .unwrap()
+        };
+        assert_eq!(config().profile_names(), ["deep", "review"]);
+
+        let deep = config().with_profile(None).unwrap().session;
+        assert_eq!(deep.duration.as_deref(), Some("90m"));
+        assert_eq!(deep.workspace.as_deref(), Some("focus"));
+        assert_eq!(deep.keep_status_bar, Some(true));
+
+        let review = config().with_profile(Some("review")).unwrap().session;
+        assert_eq!(review.duration.as_deref(), Some("25m"));
+        assert_eq!(review.workspace.as_deref(), Some("review"));
+        assert_eq!(review.keep_status_bar, None);
+
+        assert!(config().with_profile(Some("shallow")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let config = |content: &str| -> ConfigFile { toml::from_str(content).unwrap() };
+        let known = config("duration = \"25m\"\nschedule = []\n[profile.deep]\nprint-time = true");
+        assert!(known.check_unknown().is_ok());
+        let error = config("duraton = \"25m\"").check_unknown().unwrap_err();
+        assert_eq!(error.to_string(), "Unknown config key `duraton`");
+        let error = config("[profile.deep]\nprint_time = true")
+            .check_unknown()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown config key `profile.deep.print_time`"
+        );
+    }
+}
@@ -0,0 +1,625 @@
+/// This module runs focus sessions one after another. It owns the bus name `org.towoe.FocusTime`
+/// for as long as it runs, so that clients can start, queue and control sessions over D-Bus.
+///
+/// Running `focus-time` with a duration runs that session and any sessions queued in the meantime,
+/// and exits when the queue is empty. `focus-time daemon` keeps running while idle.
+///
+/// Only one instance owns the bus name. The name is checked before anything on the desktop is
+/// changed, and another instance can take it over, which stops the running session.
+///
+/// `focus-time daemon` also starts the sessions scheduled in the configuration file or with
+/// `focus-time schedule`, and those for the events of the configured calendar, when they are due.
+///
+/// Named timers, e.g. `focus-time --name oven 10m`, run next to the focus session under the bus
+/// name `org.towoe.FocusTime.<name>`. They only count down and notify, the desktop is left alone.
+use crate::calendar::Calendar;
+use crate::cli::SessionArgs;
+use crate::client;
+use crate::config;
+use crate::focus;
+use crate::focus_interface::FocusTime;
+use crate::schedule::{Entry, Schedule, Scheduled};
+use crate::service;
+use crate::session::{Command, FinishReason, SessionHandle};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use zbus::fdo::{DBusProxy, RequestNameFlags};
+use zbus::names::{BusName, OwnedUniqueName, WellKnownName};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::Connection;
+
+/// The bus name of the focus timer.
+const BUS_NAME: &str = "org.towoe.FocusTime";
+
+/// The object path of the FocusTime interface, the same for every timer.
+const OBJECT_PATH: &str = "/org/towoe/FocusTime";
+
+/// Returns the bus name of the focus timer, or of a named timer.
+///
+/// # Arguments
+///
+/// * `name` - The name of the timer, `None` for the focus timer.
+///
+/// # Returns
+///
+/// A `Result` containing the bus name, or an error if the name is not valid in a bus name.
+pub fn bus_name(name: Option<&str>) -> Result<WellKnownName<'static>> {
+    let Some(name) = name else {
+        return Ok(WellKnownName::from_static_str_unchecked(BUS_NAME));
+    };
+    let invalid = || {
+        anyhow!("Invalid timer name '{name}', use letters, digits and '_' and start with a letter")
+    };
+    if name.contains('.') {
+        return Err(invalid());
+    }
+    WellKnownName::try_from(format!("{BUS_NAME}.{name}")).map_err(|_| invalid())
+}
+
+/// Returns the name of a named timer from its bus name.
+///
+/// # Arguments
+///
+/// * `bus_name` - A bus name on the session bus.
+///
+/// # Returns
+///
+/// `Some(None)` for the focus timer, `Some(Some(name))` for a named timer, and `None` for other
+/// bus names.
+pub fn timer_name(bus_name: &str) -> Option<Option<&str>> {
+    match bus_name.strip_prefix(BUS_NAME)? {
+        "" => Some(None),
+        suffix => suffix.strip_prefix('.').map(Some),
+    }
+}
+
+/// How often the wall clock is checked for due sessions at the latest, as it can jump, e.g. after a
+/// suspend.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait for a replaced instance to restore the desktop.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to do if another instance already owns the bus name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OtherInstance {
+    /// Print the status of the other instance and fail.
+    Refuse,
+    /// Queue the session in the other instance.
+    Queue,
+    /// Stop the other instance and take over.
+    Replace,
+}
+
+/// The running focus session.
+#[derive(Clone, Debug)]
+pub struct Current {
+    /// The handle of the session.
+    pub session: SessionHandle,
+    /// The task of the session.
+    pub task: Option<String>,
+}
+
+/// The sessions of the daemon.
+#[derive(Debug, Default)]
+struct Sessions {
+    /// The running session, `None` while idle.
+    current: Option<Current>,
+    /// Sessions to run after the current one, in order.
+    queue: VecDeque<SessionArgs>,
+    /// Whether another instance took over the bus name.
+    replaced: bool,
+}
+
+/// The state of the daemon, which is shared with the D-Bus interface.
+#[derive(Debug, Default)]
+pub struct Daemon {
+    /// Path to the configuration file, which is loaded for every session.
+    config: Option<String>,
+    /// The name of the timer, `None` for the focus timer.
+    name: Option<String>,
+    sessions: Mutex<Sessions>,
+    /// Notified when a session is queued.
+    queued: Notify,
+    /// Notified when a session starts or ends.
+    current_changed: Notify,
+    /// The scheduled sessions, `None` if sessions cannot be scheduled.
+    schedule: Mutex<Option<Schedule>>,
+    /// Notified when the schedule changed.
+    schedule_changed: Notify,
+}
+
+impl Daemon {
+    /// Creates a new idle `Daemon`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Path to the configuration file, the default location if `None`.
+    /// * `name` - The name of the timer, `None` for the focus timer.
+    pub fn new(config: Option<String>, name: Option<String>) -> Self {
+        Self {
+            config,
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the running session, `None` while idle.
+    pub fn current(&self) -> Option<Current> {
+        self.sessions.lock().unwrap().current.clone()
+    }
+
+    /// Sets the running session.
+    pub fn set_current(&self, current: Option<Current>) {
+        self.sessions.lock().unwrap().current = current;
+        self.current_changed.notify_one();
+    }
+
+    /// Returns the number of queued sessions.
+    pub fn queue_len(&self) -> usize {
+        self.sessions.lock().unwrap().queue.len()
+    }
+
+    /// Queues a session after checking that a focus timer can be created for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The options of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of sessions before this one, including the running one.
+    pub fn enqueue(&self, args: SessionArgs) -> Result<usize> {
+        focus::new(&self.config, args.clone())?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let position = sessions.queue.len() + usize::from(sessions.current.is_some());
+        sessions.queue.push_back(args);
+        self.queued.notify_one();
+        Ok(position)
+    }
+
+    /// Schedules a session once, at the next occurrence of a time of the day.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of the day, e.g. `09:00`.
+    /// * `args` - The options of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the id and the start of the session.
+    pub fn schedule(&self, time: &str, args: SessionArgs) -> Result<(u32, DateTime<Utc>)> {
+        let entry = Entry::once(time, args)?;
+        focus::new(&self.config, entry.args.clone())?;
+        let mut schedule = self.schedule.lock().unwrap();
+        let schedule = schedule
+            .as_mut()
+            .ok_or_else(|| anyhow!("Sessions can only be scheduled with `focus-time daemon`"))?;
+        let scheduled = schedule.add(entry, false, Utc::now());
+        let result = (scheduled.id, scheduled.next.unwrap_or_default());
+        self.schedule_changed.notify_one();
+        Ok(result)
+    }
+
+    /// Returns the scheduled sessions, ordered by their next start.
+    pub fn scheduled(&self) -> Vec<Scheduled> {
+        match self.schedule.lock().unwrap().as_ref() {
+            Some(schedule) => schedule.entries(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Cancels a session which was scheduled with [`Daemon::schedule`].
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub fn cancel_scheduled(&self, id: u32) -> Result<()> {
+        let mut schedule = self.schedule.lock().unwrap();
+        schedule
+            .as_mut()
+            .ok_or_else(|| anyhow!("No session is scheduled with id {id}"))?
+            .cancel(id)?;
+        self.schedule_changed.notify_one();
+        Ok(())
+    }
+
+    /// Takes the next session from the queue.
+    fn next(&self) -> Option<SessionArgs> {
+        self.sessions.lock().unwrap().queue.pop_front()
+    }
+
+    /// Returns whether another instance took over the bus name.
+    fn is_replaced(&self) -> bool {
+        self.sessions.lock().unwrap().replaced
+    }
+
+    /// Gives up after another instance took over the bus name. The queue is dropped and the
+    /// running session is stopped, so that the desktop is restored before the other instance
+    /// starts its session.
+    async fn give_up(&self) {
+        warn!("Another instance took over, stopping");
+        let current = {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.replaced = true;
+            sessions.queue.clear();
+            sessions.current.clone()
+        };
+        self.queued.notify_one();
+        if let Some(current) = current {
+            if let Err(e) = current.session.send(Command::Stop).await {
+                debug!("Failed to stop the replaced session: {e}");
+            }
+        }
+    }
+}
+
+/// Runs focus sessions until the queue is empty, or forever if `persistent` is set.
+///
+/// # Arguments
+///
+/// * `config` - Path to the configuration file.
+/// * `name` - The name of the timer, `None` for the focus timer.
+/// * `first` - The session to run first.
+/// * `persistent` - Whether to keep running while idle.
+/// * `other` - What to do if another instance is already running.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation. Without `persistent`, a
+/// failing session ends the daemon with its error.
+pub async fn run(
+    config: Option<String>,
+    name: Option<String>,
+    first: Option<SessionArgs>,
+    persistent: bool,
+    other: OtherInstance,
+) -> Result<()> {
+    let bus_name = bus_name(name.as_deref())?;
+    if persistent && name.is_none() {
+        service::warn_if_out_of_date();
+    }
+    let connection = Connection::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
+    let mut replaced_owner = None;
+    if let Ok(owner) = dbus.get_name_owner(BusName::from(bus_name.clone())).await {
+        match (other, &first) {
+            (OtherInstance::Queue, Some(args)) => {
+                let proxy = client::proxy(&connection, name.as_deref()).await?;
+                return client::start(&proxy, args).await;
+            }
+            (OtherInstance::Replace, _) => replaced_owner = Some(owner),
+            _ => {
+                let proxy = client::proxy(&connection, name.as_deref()).await?;
+                if let Ok(status) = client::status_text(&proxy).await {
+                    println!("focus-time is already running: {status}");
+                }
+                return Err(anyhow!(
+                    "Another instance owns {bus_name}, use --queue to run this session after \
+                     the running one or --replace to take over"
+                ));
+            }
+        }
+    }
+
+    let daemon = Arc::new(Daemon::new(config, name));
+    if let Some(args) = first {
+        daemon.enqueue(args)?;
+    }
+    if persistent && daemon.name.is_none() {
+        let file_config = config::load_from_file(&daemon.config)?;
+        let now = Utc::now();
+        let configs = file_config.schedule.as_deref().unwrap_or_default();
+        for profile in configs
+            .iter()
+            .filter_map(|config| config.profile.as_deref())
+        {
+            file_config.check_profile(profile)?;
+        }
+        let mut schedule = Schedule::from_config(configs, now)?;
+        if let Some(calendar) = &file_config.calendar {
+            schedule = schedule.with_calendar(Calendar::from_config(calendar)?, now);
+        }
+        *daemon.schedule.lock().unwrap() = Some(schedule);
+        tokio::spawn(run_schedule(Arc::clone(&daemon)));
+    }
+    let mut name_lost = dbus
+        .receive_name_lost_with_args(&[(0, bus_name.as_str())])
+        .await?;
+    serve(&daemon, &connection, &dbus, &bus_name, replaced_owner).await?;
+    tokio::spawn(publish_state(Arc::clone(&daemon), connection.clone()));
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if name_lost.next().await.is_some() {
+                daemon.give_up().await;
+            }
+        }
+    });
+
+    loop {
+        if daemon.is_replaced() {
+            break;
+        }
+        let Some(mut args) = daemon.next() else {
+            if !persistent {
+                break;
+            }
+            info!("Idle, waiting for a session to start");
+            tokio::select! {
+                _ = daemon.queued.notified() => continue,
+                _ = focus::terminated() => break,
+            }
+        };
+        if let Some(until) = args.until {
+            // The session may start late, after the sessions queued before it
+            let left = (until - Utc::now()).num_seconds();
+            if left <= 0 {
+                info!("Skipping session which ended while it was queued: {args:?}");
+                continue;
+            }
+            args.duration = Some(format!("{left}s"));
+        }
+        debug!("Starting session: {args:?}");
+        let result = async {
+            let focus = focus::new(&daemon.config, args)?;
+            match &daemon.name {
+                Some(name) => focus.run_timer(name, &daemon).await,
+                None => focus.run(&daemon, &connection).await,
+            }
+        }
+        .await;
+        daemon.set_current(None);
+        match result {
+            // Ctrl+C ends the daemon together with the session
+            Ok(FinishReason::Aborted) => break,
+            Ok(_) => {}
+            Err(e) if persistent => eprintln!("Focus session failed: {e:#}"),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Queues the scheduled sessions when they are due.
+///
+/// # Arguments
+///
+/// * `daemon` - The daemon with the schedule.
+async fn run_schedule(daemon: Arc<Daemon>) {
+    loop {
+        let now = Utc::now();
+        let (due, next) = {
+            let mut schedule = daemon.schedule.lock().unwrap();
+            let Some(schedule) = schedule.as_mut() else {
+                return;
+            };
+            (schedule.take_due(now), schedule.next_start())
+        };
+        for args in due {
+            if let Err(e) = daemon.enqueue(args) {
+                eprintln!("Scheduled session failed: {e:#}");
+            }
+        }
+        let wait = match next {
+            Some(next) => (next - now).to_std().unwrap_or_default(),
+            None => SCHEDULE_INTERVAL,
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(wait.min(SCHEDULE_INTERVAL)) => {},
+            _ = daemon.schedule_changed.notified() => {},
+        }
+    }
+}
+
+/// Emits `StateChanged` when a session starts or ends, and whenever the running session changes
+/// its phase or its interruptions, so that clients like the focus bar do not have to poll.
+///
+/// # Arguments
+///
+/// * `daemon` - The daemon, whose sessions are published.
+/// * `connection` - The session bus connection.
+async fn publish_state(daemon: Arc<Daemon>, connection: Connection) {
+    let emitter = match SignalEmitter::new(&connection, OBJECT_PATH) {
+        Ok(emitter) => emitter,
+        Err(e) => return warn!("Failed to publish the session state: {e}"),
+    };
+    loop {
+        let mut state = daemon.current().map(|current| current.session.subscribe());
+        let phase = state
+            .as_mut()
+            .map_or("idle", |state| state.borrow_and_update().phase.name());
+        if let Err(e) = FocusTime::state_changed(&emitter, phase).await {
+            debug!("Failed to emit StateChanged: {e}");
+        }
+        tokio::select! {
+            _ = daemon.current_changed.notified() => {},
+            // Fails once the session has ended, then only the daemon is waited for
+            Some(Ok(())) = async { Some(state.as_mut()?.changed().await) } => {},
+        }
+    }
+}
+
+/// Registers the FocusTime interface and requests the bus name. The name can be taken over by
+/// another instance.
+///
+/// # Arguments
+///
+/// * `daemon` - The daemon to serve.
+/// * `connection` - The session bus connection.
+/// * `dbus` - The proxy of the message bus.
+/// * `bus_name` - The bus name to request.
+/// * `replaced_owner` - The instance to take the name over from, which is waited for until it
+///   has restored the desktop and exited.
+///
+/// # Client example
+///
+/// $ busctl --user call org.towoe.FocusTime /org/towoe/FocusTime org.towoe.FocusTime GetRemainingTime
+async fn serve(
+    daemon: &Arc<Daemon>,
+    connection: &Connection,
+    dbus: &DBusProxy<'_>,
+    bus_name: &WellKnownName<'_>,
+    replaced_owner: Option<OwnedUniqueName>,
+) -> Result<()> {
+    debug!("Starting D-Bus service");
+    connection
+        .object_server()
+        .at(
+            OBJECT_PATH,
+            FocusTime {
+                daemon: Arc::clone(daemon),
+            },
+        )
+        .await?;
+    let flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
+    let Some(owner) = replaced_owner else {
+        connection
+            .request_name_with_flags(bus_name.clone(), flags)
+            .await?;
+        return Ok(());
+    };
+
+    info!("Taking over from {owner}");
+    let mut owner_changed = dbus
+        .receive_name_owner_changed_with_args(&[(0, owner.as_str())])
+        .await?;
+    connection
+        .request_name_with_flags(bus_name.clone(), flags | RequestNameFlags::ReplaceExisting)
+        .await
+        .map_err(|e| anyhow!("Failed to take over from the running instance: {e}"))?;
+    let exited = async {
+        while let Some(signal) = owner_changed.next().await {
+            if signal.args()?.new_owner.is_none() {
+                break;
+            }
+        }
+        zbus::Result::Ok(())
+    };
+    match tokio::time::timeout(REPLACE_TIMEOUT, exited).await {
+        Ok(result) => result?,
+        Err(_) => warn!("The replaced instance did not exit in time"),
+    }
+    Ok(())
+}
+
+/// Converts the session options into the options of the D-Bus `Start` method.
+///
+/// # Arguments
+///
+/// * `args` - The session options, the duration is passed separately.
+///
+/// # Returns
+///
+/// The options, only those which are set are included.
+pub fn to_options(args: &SessionArgs) -> HashMap<&'static str, Value<'_>> {
+    let mut options = HashMap::new();
+    let flags = [
+        ("no-notification", args.no_notification),
+        ("keep-status-bar", args.keep_status_bar),
+        ("print-time", args.print_time),
+        ("focus-bar", args.focus_bar),
+        ("move-window", args.move_window),
+    ];
+    for (key, value) in flags {
+        if value {
+            options.insert(key, Value::Bool(true));
+        }
+    }
+    if let Some(task) = &args.task {
+        options.insert("task", Value::from(task.as_str()));
+    }
+    if let Some(workspace) = &args.workspace {
+        options.insert("workspace", Value::from(workspace.as_str()));
+    }
+    if let Some(profile) = &args.profile {
+        options.insert("profile", Value::from(profile.as_str()));
+    }
+    options
+}
+
+/// Converts the arguments of the D-Bus `Start` method back into session options.
+///
+/// # Arguments
+///
+/// * `duration` - The duration of the session, empty for the configured duration.
+/// * `options` - The options, see [`to_options`].
+///
+/// # Returns
+///
+/// A `Result` containing the session options, or an error for unknown or mistyped options.
+pub fn from_options(duration: &str, options: &HashMap<String, OwnedValue>) -> Result<SessionArgs> {
+    let mut args = SessionArgs {
+        duration: (!duration.is_empty()).then(|| duration.to_string()),
+        ..Default::default()
+    };
+    for (key, value) in options {
+        let flag = || value.downcast_ref::<bool>();
+        let text = || value.downcast_ref::<&str>().map(str::to_string);
+        match key.as_str() {
+            "no-notification" => args.no_notification = flag()?,
+            "keep-status-bar" => args.keep_status_bar = flag()?,
+            "print-time" => args.print_time = flag()?,
+            "focus-bar" => args.focus_bar = flag()?,
+            "move-window" => args.move_window = flag()?,
+            "task" => args.task = Some(text()?),
+            "workspace" => args.workspace = Some(text()?),
+            "profile" => args.profile = Some(text()?),
+            _ => return Err(anyhow!("Unknown session option '{key}'")),
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_roundtrip() {
+        let args = SessionArgs {
+            duration: Some("50m".to_string()),
+            focus_bar: true,
+            task: Some("Write report".to_string()),
+            workspace: Some("focus".to_string()),
+            profile: Some("deep".to_string()),
+            ..Default::default()
+        };
+        let options = to_options(&args)
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.try_to_owned().unwrap()))
+            .collect();
+        assert_eq!(from_options("50m", &options).unwrap(), args);
+
+        let unknown = HashMap::from([("color".to_string(), OwnedValue::from(true))]);
+        assert!(from_options("", &unknown).is_err());
+        let mistyped = HashMap::from([("task".to_string(), OwnedValue::from(true))]);
+        assert!(from_options("", &mistyped).is_err());
+    }
+
+    #[test]
+    fn test_bus_name() {
+        assert_eq!(bus_name(None).unwrap(), "org.towoe.FocusTime");
+        assert_eq!(bus_name(Some("oven")).unwrap(), "org.towoe.FocusTime.oven");
+        assert!(bus_name(Some("2nd")).is_err());
+        assert!(bus_name(Some("a.b")).is_err());
+        assert!(bus_name(Some("")).is_err());
+
+        assert_eq!(timer_name("org.towoe.FocusTime"), Some(None));
+        assert_eq!(timer_name("org.towoe.FocusTime.oven"), Some(Some("oven")));
+        assert_eq!(timer_name("org.towoe.FocusTimer"), None);
+        assert_eq!(timer_name("org.freedesktop.Notifications"), None);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::config::{BusType, DbusActionConfig, SessionEvent};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::str::FromStr;
+use zbus::zvariant::{Array, Dict, ObjectPath, Signature, Structure, StructureBuilder, Value};
+use zbus::Connection;
+
+/// A D-Bus method call, which is made when a session event occurs.
+#[derive(Debug)]
+struct DbusAction {
+    event: SessionEvent,
+    bus: BusType,
+    destination: String,
+    path: String,
+    interface: String,
+    method: String,
+    /// The arguments, converted according to the signature. `None` if there are no arguments.
+    body: Option<Structure<'static>>,
+}
+
+/// The D-Bus method calls for all session events.
+#[derive(Debug, Default)]
+pub struct DbusActions {
+    actions: Vec<DbusAction>,
+}
+
+impl DbusActions {
+    /// Creates new `DbusActions` from the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `configs` - The configured method calls.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DbusActions`, or an error if a signature is invalid or the
+    /// arguments do not match it.
+    pub fn from_config(configs: &[DbusActionConfig]) -> Result<Self> {
+        let actions = configs
+            .iter()
+            .map(|config| {
+                let body = to_body(config.signature.as_deref().unwrap_or(""), &config.args)
+                    .with_context(|| {
+                        format!(
+                            "Invalid arguments for D-Bus action {}.{}",
+                            config.interface, config.method
+                        )
+                    })?;
+                Ok(DbusAction {
+                    event: config.event,
+                    bus: config.bus.unwrap_or_default(),
+                    destination: config.destination.clone(),
+                    path: config.path.clone(),
+                    interface: config.interface.clone(),
+                    method: config.method.clone(),
+                    body,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { actions })
+    }
+
+    /// Makes the method calls configured for a session event.
+    ///
+    /// All calls are made, even if one of them fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The session event that occurred.
+    /// * `session` - The connection to the session bus.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the calls, the first error is returned.
+    pub async fn run(&self, event: SessionEvent, session: &Connection) -> Result<()> {
+        let mut system = None;
+        let mut result = Ok(());
+        for action in self.actions.iter().filter(|action| action.event == event) {
+            let connection = match action.bus {
+                BusType::Session => session,
+                BusType::System => match &system {
+                    Some(system) => system,
+                    None => match Connection::system().await {
+                        Ok(connection) => system.insert(connection),
+                        Err(e) => {
+                            let e = anyhow::Error::new(e).context(format!(
+                                "D-Bus action {}.{} failed to connect to the system bus",
+                                action.interface, action.method
+                            ));
+                            result = result.and(Err(e));
+                            continue;
+                        }
+                    },
+                },
+            };
+            debug!(
+                "Calling {}.{} on {} {} for {event:?}",
+                action.interface, action.method, action.destination, action.path
+            );
+            let destination = Some(action.destination.as_str());
+            let interface = Some(action.interface.as_str());
+            let path = action.path.as_str();
+            let method = action.method.as_str();
+            let call = match &action.body {
+                Some(body) => {
+                    connection
+                        .call_method(destination, path, interface, method, body)
+                        .await
+                }
+                None => {
+                    connection
+                        .call_method(destination, path, interface, method, &())
+                        .await
+                }
+            }
+            .with_context(|| format!("D-Bus action {}.{} failed", action.interface, action.method));
+            if let (Ok(()), Err(e)) = (&result, call) {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+/// Converts the configured arguments into a message body with the given signature.
+fn to_body(signature: &str, args: &[toml::Value]) -> Result<Option<Structure<'static>>> {
+    if signature.is_empty() && args.is_empty() {
+        return Ok(None);
+    }
+    // Parse the arguments as one structure, so that a single struct argument stays a struct
+    let signature = parse_signature(&format!("({signature})"))?;
+    let Signature::Structure(fields) = &signature else {
+        return Err(anyhow!("Invalid signature '{signature}' for the arguments"));
+    };
+    if fields.len() != args.len() {
+        return Err(anyhow!(
+            "Expected {} arguments, got {}",
+            fields.len(),
+            args.len()
+        ));
+    }
+    let mut body = StructureBuilder::new();
+    for (signature, arg) in fields.iter().zip(args) {
+        body = body.append_field(to_value(signature, arg)?);
+    }
+    Ok(Some(body.build()?))
+}
+
+/// Converts a TOML value into a D-Bus value with the given signature.
+fn to_value(signature: &Signature, value: &toml::Value) -> Result<Value<'static>> {
+    use toml::Value as Toml;
+
+    let mismatch = || anyhow!("Value {value} does not match signature '{signature}'");
+    let int = || value.as_integer().ok_or_else(mismatch);
+    let value = match (signature, value) {
+        (Signature::Bool, Toml::Boolean(b)) => Value::Bool(*b),
+        (Signature::U8, _) => Value::U8(int()?.try_into()?),
+        (Signature::I16, _) => Value::I16(int()?.try_into()?),
+        (Signature::U16, _) => Value::U16(int()?.try_into()?),
+        (Signature::I32, _) => Value::I32(int()?.try_into()?),
+        (Signature::U32, _) => Value::U32(int()?.try_into()?),
+        (Signature::I64, _) => Value::I64(int()?),
+        (Signature::U64, _) => Value::U64(int()?.try_into()?),
+        (Signature::F64, Toml::Float(f)) => Value::F64(*f),
+        (Signature::F64, Toml::Integer(i)) => Value::F64(*i as f64),
+        (Signature::Str, Toml::String(s)) => Value::from(s.clone()),
+        (Signature::ObjectPath, Toml::String(s)) => {
+            Value::ObjectPath(ObjectPath::try_from(s.clone())?)
+        }
+        (Signature::Signature, Toml::String(s)) => Value::Signature(parse_signature(s)?),
+        (Signature::Variant, _) => Value::Value(Box::new(to_value(&infer(value)?, value)?)),
+        (Signature::Array(child), Toml::Array(elements)) => {
+            let mut array = Array::new(child);
+            for element in elements {
+                array.append(to_value(child, element)?)?;
+            }
+            Value::Array(array)
+        }
+        (Signature::Dict { key, value }, Toml::Table(table)) => {
+            let mut dict = Dict::new(key, value);
+            for (k, v) in table {
+                dict.append(
+                    to_value(key, &Toml::String(k.clone()))?,
+                    to_value(value, v)?,
+                )?;
+            }
+            Value::Dict(dict)
+        }
+        (Signature::Structure(fields), Toml::Array(elements)) if fields.len() == elements.len() => {
+            let mut structure = StructureBuilder::new();
+            for (field, element) in fields.iter().zip(elements) {
+                structure = structure.append_field(to_value(field, element)?);
+            }
+            Value::Structure(structure.build()?)
+        }
+        _ => return Err(mismatch()),
+    };
+    Ok(value)
+}
+
+/// Parses a D-Bus signature.
+fn parse_signature(signature: &str) -> Result<Signature> {
+    Signature::from_str(signature)
+        .map_err(|e| anyhow!("Invalid D-Bus signature '{signature}': {e}"))
+}
+
+/// Chooses the signature for a value inside a variant from its TOML type.
+fn infer(value: &toml::Value) -> Result<Signature> {
+    use toml::Value as Toml;
+
+    Ok(match value {
+        Toml::Boolean(_) => Signature::Bool,
+        Toml::Integer(i) if i32::try_from(*i).is_ok() => Signature::I32,
+        Toml::Integer(_) => Signature::I64,
+        Toml::Float(_) => Signature::F64,
+        Toml::String(_) => Signature::Str,
+        Toml::Array(elements) => Signature::array(match elements.first() {
+            Some(first) => infer(first)?,
+            None => Signature::Variant,
+        }),
+        Toml::Table(_) => Signature::dict(Signature::Str, Signature::Variant),
+        Toml::Datetime(_) => Err(anyhow!("Dates are not supported in D-Bus arguments"))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// The arguments of a call to the stand-in service.
+    type Call = (String, u32, Vec<String>);
+
+    /// A stand-in service that records the calls it receives.
+    struct Recorder {
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    #[zbus::interface(name = "org.towoe.FocusTime.Test")]
+    impl Recorder {
+        fn record(&self, name: String, count: u32, tags: Vec<String>) {
+            self.calls.lock().unwrap().push((name, count, tags));
+        }
+    }
+
+    fn action(event: &str, method: &str, signature: &str, args: &str) -> DbusActionConfig {
+        toml::from_str(&format!(
+            r#"
+            event = "{event}"
+            destination = "org.towoe.FocusTime.Test"
+            path = "/test"
+            interface = "org.towoe.FocusTime.Test"
+            method = "{method}"
+            signature = "{signature}"
+            args = {args}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_against_mock_service() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let recorder = Recorder {
+            calls: Arc::clone(&calls),
+        };
+        let (_server, client) = tokio::try_join!(
+            zbus::connection::Builder::unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/test", recorder)
+                .unwrap()
+                .build(),
+            zbus::connection::Builder::unix_stream(client).p2p().build(),
+        )
+        .unwrap();
+
+        let actions = DbusActions::from_config(&[
+            action("start", "Record", "suas", r#"["start", 1, ["a", "b"]]"#),
+            action("end", "Record", "suas", r#"["end", 2, []]"#),
+        ])
+        .unwrap();
+        actions.run(SessionEvent::Start, &client).await.unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(
+                "start".to_string(),
+                1,
+                vec!["a".to_string(), "b".to_string()]
+            )]
+        );
+
+        let failing = DbusActions::from_config(&[action("end", "Missing", "", "[]")]).unwrap();
+        assert!(failing.run(SessionEvent::End, &client).await.is_err());
+    }
+
+    #[test]
+    fn test_to_body() {
+        let args: Vec<toml::Value> = toml::from_str::<toml::Table>(
+            r#"args = ["focus", 25, { active = true }, ["deep", 3]]"#,
+        )
+        .unwrap()["args"]
+            .as_array()
+            .unwrap()
+            .clone();
+        let body = to_body("sua{sv}(si)", &args).unwrap().unwrap();
+        assert_eq!(body.signature().to_string(), "(sua{sv}(si))");
+        assert!(to_body("", &[]).unwrap().is_none());
+        assert!(to_body("", &args).is_err());
+
+        assert!(to_body("s", &args).is_err());
+        assert!(to_body("ssa{sv}(si)", &args).is_err());
+        assert!(to_body(
+            "sya{sv}(si)",
+            &[
+                args[0].clone(),
+                300.into(),
+                args[2].clone(),
+                args[3].clone()
+            ]
+        )
+        .is_err());
+    }
+}
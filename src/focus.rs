@@ -1,38 +1,42 @@
-use crate::cli::Cli;
-use crate::config::{self, ConfigFile};
+use crate::auto_pause::AutoPause;
+use crate::bar::ExternalBar;
+use crate::bindings::{Bindings, BindingsSession};
+use crate::calendar::{Calendar, Overlap};
+use crate::cli::SessionArgs;
+use crate::config::{self, ConfigFile, MediaConfig, SessionEvent};
+use crate::daemon::{Current, Daemon};
+use crate::dbus_actions::DbusActions;
 use crate::focus;
-use crate::focus_interface::FocusTime;
+use crate::guard::Guard;
+use crate::inhibit::Inhibit;
+use crate::mpris_interface::MprisInterface;
 use crate::notification_interface::NotificationInterface;
+use crate::outputs::{self, OutputState, Outputs};
+use crate::processes::{self, Processes};
+use crate::recovery::{self, RecoveryState};
+use crate::session::{Command, FinishReason, Phase, Request, SessionHandle, SessionState};
+use crate::stash::{Stash, StashSession};
+use crate::sway_commands::SwayCommands;
 use crate::sway_ipc_interface::SwayIpcInterface;
 use crate::swaync_interface::SwayNCInterface;
 use crate::timer::Timer;
+use crate::visual::{Visual, VisualSession};
 
 use anyhow::Result;
+use chrono::Utc;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::collections::HashMap;
 use std::time::Duration;
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
-use tokio::sync::oneshot;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use zbus::zvariant::Value;
 use zbus::Connection;
 
 use log::{debug, error, info, trace};
 
-/// Represents the possible signals that can abort the focus timer.
-#[derive(PartialEq)]
-pub enum Signal {
-    /// Signal for D-Bus interruption.
-    Dbus,
-    /// Signal for Ctrl+C interruption.
-    CtrlC,
-    /// Signal for pausing/resuming the timer.
-    TogglePause,
-}
-
 /// Configuration for the focus timer.
 /// This is the config derived from the config file and the command line arguments. It's used to
 /// control the behaviour of the focus timer.
@@ -46,25 +50,132 @@ pub struct FocusConfig {
     keep_status_bar: bool,
     /// Whether to print the remaining time.
     print_time: bool,
+    /// Whether to replace the status bar contents instead of hiding the bar.
+    focus_bar: bool,
+    /// Task to work on during the focus session.
+    task: Option<String>,
+    /// Workspace to switch to during the focus session.
+    workspace: Option<String>,
+    /// Whether to move the focused window to the focus workspace.
+    move_window: bool,
+    /// External status bars which are hidden together with the sway bars.
+    bars: Vec<ExternalBar>,
+    /// Windows to move out of sight during the session.
+    stash: Option<Stash>,
+    /// Guard against focusing blocked windows.
+    guard: Option<Guard>,
+    /// Visual cues applied to sway during the session.
+    visual: Option<Visual>,
+    /// Binding mode and key bindings for the session.
+    bindings: Option<Bindings>,
+    /// Outputs to turn off during the session.
+    outputs: Option<Outputs>,
+    /// Sway commands to run at the start and the end of the session.
+    sway_commands: SwayCommands,
+    /// D-Bus methods to call on session events.
+    dbus_actions: DbusActions,
+    /// Media players to control during the session.
+    media: Option<MediaConfig>,
+    /// Processes to stop during the session.
+    processes: Option<Processes>,
+    /// Operations to inhibit during the session.
+    inhibit: Option<Inhibit>,
+    /// Pausing the timer while the user is away.
+    auto_pause: Option<AutoPause>,
+    /// Calendar with the commitments the session has to end before.
+    calendar: Option<Calendar>,
 }
 
-/// Creates a `FocusConfig` from the provided `ConfigFile` and `Cli` arguments.
+/// Creates a `FocusConfig` from the provided `ConfigFile` and session arguments.
 ///
 /// # Arguments
 ///
 /// * `file_config` - A `ConfigFile` containing configuration loaded from a file.
-/// * `args` - A `Cli` struct containing command line arguments.
+/// * `args` - The options of the session, from the command line or from D-Bus.
 ///
 /// # Returns
 ///
 /// A `FocusConfig` struct containing the merged configuration.
-pub fn create_config(file_config: ConfigFile, args: Cli) -> anyhow::Result<FocusConfig> {
+pub fn create_config(file_config: ConfigFile, args: SessionArgs) -> anyhow::Result<FocusConfig> {
+    let ConfigFile {
+        session: file_config,
+        calendar: calendar_config,
+        ..
+    } = file_config.with_profile(args.profile.as_deref())?;
     let duration = get_duration(&args.duration, &file_config.duration)?;
+    let bars = file_config
+        .bar
+        .iter()
+        .flatten()
+        .map(ExternalBar::from_config)
+        .collect::<Result<_>>()?;
+    let stash = file_config
+        .stash
+        .as_ref()
+        .map(Stash::from_config)
+        .transpose()?;
+    let guard = file_config
+        .guard
+        .as_ref()
+        .map(Guard::from_config)
+        .transpose()?;
+    let sway_config = file_config.sway.unwrap_or_default();
+    let visual = sway_config.visual.map(Visual::new);
+    let sway_commands =
+        SwayCommands::from_config(sway_config.on_start.unwrap_or_default(), sway_config.on_end);
+    let bindings = match (sway_config.mode, sway_config.bindings) {
+        (None, None) => None,
+        (mode, bindings) => Some(Bindings::new(mode, bindings.unwrap_or_default())),
+    };
+    if let Some(MediaConfig {
+        player: None,
+        uri: Some(uri),
+        ..
+    }) = &file_config.media
+    {
+        return Err(anyhow::anyhow!(
+            "Media URI '{uri}' is configured without a player"
+        ));
+    }
+    let auto_pause = file_config
+        .auto_pause
+        .as_ref()
+        .map(AutoPause::from_config)
+        .transpose()?;
+    let processes = file_config
+        .processes
+        .as_ref()
+        .map(Processes::from_config)
+        .transpose()?;
+    let calendar = calendar_config
+        .as_ref()
+        .map(Calendar::from_config)
+        .transpose()?;
     Ok(FocusConfig {
         duration,
         no_notification: args.no_notification || file_config.no_notification.unwrap_or(false),
         keep_status_bar: args.keep_status_bar || file_config.keep_status_bar.unwrap_or(false),
         print_time: args.print_time || file_config.print_time.unwrap_or(false),
+        focus_bar: args.focus_bar || file_config.focus_bar.unwrap_or(false),
+        task: args.task,
+        workspace: args.workspace.or(file_config.workspace),
+        move_window: args.move_window || file_config.move_window.unwrap_or(false),
+        bars,
+        stash,
+        guard,
+        visual,
+        bindings,
+        outputs: file_config.outputs.map(Outputs::new),
+        sway_commands,
+        dbus_actions: DbusActions::from_config(&file_config.dbus_actions.unwrap_or_default())?,
+        media: file_config.media,
+        processes,
+        inhibit: file_config
+            .inhibit
+            .filter(|what| !what.is_empty())
+            .map(Inhibit::new),
+        auto_pause,
+        calendar,
     })
 }
 
@@ -139,176 +250,666 @@ pub fn parse_duration(input: &str) -> Option<Duration> {
     }
 }
 
-/// Represents the focus timer with its configuration, timer, and channels for abort signals.
+/// Changes the phase of the session and publishes the new state.
+///
+/// # Arguments
+///
+/// * `state` - The sender of the session state.
+/// * `phase` - The new phase.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the change is allowed in the current phase.
+fn change_phase(state: &watch::Sender<SessionState>, phase: Phase) -> Result<()> {
+    let mut result = Ok(());
+    state.send_if_modified(|state| {
+        result = state.change_to(phase);
+        result.is_ok()
+    });
+    result
+}
+
+/// Quotes an argument for the shell which runs the status command of a bar.
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Waits until the process is asked to stop, either with Ctrl+C or with `SIGTERM`, which systemd
+/// sends on `systemctl --user stop` and at logout.
+pub async fn terminated() {
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            error!("Failed to listen for SIGTERM: {e}");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = sigterm.recv() => {},
+    }
+}
+
+/// Counts the timer of a session down, while processing the commands to the session.
+///
+/// # Arguments
+///
+/// * `state` - The sender of the session state.
+/// * `commands` - The receiver of the commands to the session.
+/// * `pause_changed` - Called when the timer is paused or resumed, with the new pause state.
+///
+/// # Returns
+///
+/// A `Result` containing the reason why the countdown ended.
+async fn count_down(
+    state: &watch::Sender<SessionState>,
+    commands: &mut mpsc::Receiver<Request>,
+    mut pause_changed: impl AsyncFnMut(bool),
+) -> Result<FinishReason> {
+    // The hooks for pausing and resuming react to changes of the state
+    let mut hooks = state.subscribe();
+    let mut was_paused = false;
+
+    change_phase(state, Phase::Running)?;
+    loop {
+        let (current_duration, paused) = {
+            let timer = state.borrow().timer;
+            (timer.remaining(), timer.is_paused())
+        };
+
+        if current_duration.is_zero() {
+            change_phase(state, Phase::Overtime)?;
+            return Ok(FinishReason::Completed);
+        }
+
+        tokio::select! {
+            // A paused timer does not run out, e.g. during a long break while the user is away
+            _ = sleep(current_duration), if !paused => {
+                change_phase(state, Phase::Overtime)?;
+                return Ok(FinishReason::Completed);
+            },
+            _ = terminated() => {
+                println!("\x1B[2K\rFocus timer aborted at: {}", state.borrow().timer);
+                debug!("\nReceived Ctrl+C or SIGTERM, starting cleanup...");
+                return Ok(FinishReason::Aborted);
+            },
+            Some((command, reply)) = commands.recv() => {
+                debug!("Received command: {command:?}");
+                let mut result = Ok(());
+                state.send_if_modified(|state| {
+                    let before = (state.phase, state.interruptions);
+                    result = state.apply(command);
+                    before != (state.phase, state.interruptions)
+                });
+                let _ = reply.send(result.map(|()| state.borrow().clone()));
+                if command == Command::Stop {
+                    debug!("\nReceived D-Bus stop command, starting cleanup...");
+                    return Ok(FinishReason::Stopped);
+                }
+            },
+            Ok(()) = hooks.changed() => {
+                let paused = hooks.borrow_and_update().timer.is_paused();
+                if paused != was_paused {
+                    debug!("Timer pause changed: paused = {paused}");
+                    was_paused = paused;
+                    pause_changed(paused).await;
+                }
+            },
+        }
+    }
+}
+
+/// Sends an urgent notification.
+///
+/// # Arguments
+///
+/// * `summary` - The summary of the notification.
+/// * `body` - The body of the notification.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+async fn send_notification(summary: &str, body: &str) -> Result<()> {
+    let mut hints = HashMap::new();
+    hints.insert("urgency", &Value::U8(2));
+    let notify = NotificationInterface::new().await?;
+    notify.notify(summary, body, hints).await?;
+    Ok(())
+}
+
+/// Collects the failed steps of the cleanup at the end of a session.
+#[derive(Default)]
+struct CleanupReport {
+    /// Description of each failed step with its error.
+    failures: Vec<(&'static str, anyhow::Error)>,
+}
+
+impl CleanupReport {
+    /// Records the result of a cleanup step.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - A description of the step.
+    /// * `result` - The result of the step.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the step succeeded.
+    fn step<E: Into<anyhow::Error>>(&mut self, step: &'static str, result: Result<(), E>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                let e = e.into();
+                debug!("{step} failed: {e:#}");
+                self.failures.push((step, e));
+                false
+            }
+        }
+    }
+
+    /// Prints a summary of the failed steps.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an error if any step failed.
+    fn finish(self) -> Result<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        eprintln!("Cleanup incomplete, the following steps failed:");
+        for (step, e) in &self.failures {
+            eprintln!("  - {step}: {e:#}");
+        }
+        Err(anyhow::anyhow!(
+            "{} of the cleanup steps failed",
+            self.failures.len()
+        ))
+    }
+}
+
+/// The changes made to the desktop by the setup of a session, which the cleanup undoes.
+#[derive(Default)]
+struct Applied {
+    /// Whether do not disturb was enabled.
+    dnd: bool,
+    /// Whether the status commands of the sway bars were replaced.
+    status_commands: bool,
+    /// Whether the sway bars were made invisible.
+    bars_invisible: bool,
+    /// The original state of the outputs that are turned off.
+    output_states: Vec<OutputState>,
+    /// The PIDs of the stopped processes.
+    stopped_processes: Vec<i32>,
+    /// The windows which were stashed.
+    stash_session: Option<StashSession>,
+    /// The task of the distraction guard.
+    guard_task: Option<JoinHandle<()>>,
+    /// Whether the focus workspace was switched to.
+    workspace: bool,
+    /// The visual cues which were applied.
+    visual_session: Option<VisualSession>,
+    /// The key bindings which were added.
+    bindings_session: Option<BindingsSession>,
+}
+
+/// Represents the focus timer with its configuration.
 pub struct Focus {
     /// Configuration for the focus timer.
     config: FocusConfig,
-    /// Timer for the focus session.
-    timer: Arc<Mutex<Timer>>,
-    /// Sender for abort signals.
-    tx: Arc<Mutex<Option<oneshot::Sender<Signal>>>>,
 }
 
-/// Creates a new `Focus` instance with the provided command line arguments.
+/// Creates a new `Focus` instance with the provided session arguments.
 ///
 /// # Arguments
 ///
-/// * `args` - A `Cli` struct containing command line arguments.
+/// * `config_path` - Path to the configuration file, the default location if `None`.
+/// * `args` - The options of the session, from the command line or from D-Bus.
 ///
 /// # Returns
 ///
 /// A `Result` containing the new `Focus` instance or an error message.
-pub fn new(args: Cli) -> anyhow::Result<Focus> {
+pub fn new(config_path: &Option<String>, args: SessionArgs) -> anyhow::Result<Focus> {
     info!("Loading file config");
-    let file_config = config::load_from_file(&args.config)?;
+    let file_config = config::load_from_file(config_path)?;
 
     info!("Creating focus timer configuration");
     let config = focus::create_config(file_config, args)?;
 
-    let (tx, _rx) = oneshot::channel();
-    let tx = Arc::new(Mutex::new(Some(tx)));
-    let timer = Arc::new(Mutex::new(Timer::new(config.duration)));
-
-    Ok(Focus { config, timer, tx })
+    Ok(Focus { config })
 }
 
 impl Focus {
     /// Runs the focus timer.
     ///
     /// This function initializes the necessary interfaces, sets up the environment,
-    /// and waits for the specified duration or an abort signal.
+    /// and waits for the specified duration or an abort signal. Commands to the session are
+    /// processed here, and every change is published to the subscribers of the session state.
+    ///
+    /// # Arguments
+    ///
+    /// * `daemon` - The daemon, which gives D-Bus clients access to the session.
+    /// * `dbus_conn` - The connection to the session bus.
     ///
     /// # Returns
     ///
-    /// A `Result` indicating the success or failure of the operation.
-    pub async fn run(&self) -> Result<()> {
+    /// A `Result` containing the reason why the session finished.
+    pub async fn run(&self, daemon: &Daemon, dbus_conn: &Connection) -> Result<FinishReason> {
+        let overlap = self.overlap();
+        let duration = match &overlap {
+            Some(overlap) if overlap.shortened => overlap.available,
+            _ => self.config.duration,
+        };
+        if let Some(overlap) = &overlap {
+            println!("{overlap}");
+        }
+        let state = SessionState::new(Timer::new(duration));
+        let (session, mut commands, state_tx) = SessionHandle::new(state);
+
+        // Undo what a crashed session left behind before capturing the current state
+        recovery::recover().await?;
+
         // Initialize the interfaces
         let swaync = SwayNCInterface::new().await?;
         let mut sway = SwayIpcInterface::new().await?;
+        let system_conn = match (&self.config.inhibit, &self.config.auto_pause) {
+            (None, None) => None,
+            _ => Some(Connection::system().await?),
+        };
+        let inhibit_lock = match (&self.config.inhibit, &system_conn) {
+            (Some(inhibit), Some(system_conn)) => Some(inhibit.acquire(system_conn).await?),
+            _ => None,
+        };
 
         let bar_modes = sway.get_bar_mode().await;
+        let status_commands = if self.config.focus_bar {
+            sway.get_status_commands().await?
+        } else {
+            Vec::new()
+        };
+        let mut bars = self.config.bars.clone();
+        let previous_workspace = match self.config.workspace {
+            Some(_) => Some(sway.get_focused_workspace().await?),
+            None => None,
+        };
 
-        // Set the tools to the desired state
-        swaync.enable_dnd().await?;
-        if !self.config.keep_status_bar {
-            sway.set_bars_invisible().await?;
-        }
+        // Set the tools to the desired state. If a step fails, the steps before it are undone
+        // by the cleanup below.
+        let mut applied = Applied::default();
+        let setup = self
+            .set_up(
+                &mut sway,
+                &swaync,
+                &session,
+                &status_commands,
+                &mut bars,
+                &mut applied,
+            )
+            .await;
+        let started = setup.is_ok();
 
-        if self.config.print_time {
-            let timer_clone = Arc::clone(&self.timer);
-            tokio::spawn(async move {
-                crate::timer::print_remaining_time_with_pause(timer_clone).await;
-            });
-        }
+        let mut mpris = None;
+        let mut auto_pause_task = None;
+        let result = match setup {
+            Ok(()) => {
+                if self.config.print_time {
+                    let state = session.subscribe();
+                    tokio::spawn(async move {
+                        crate::timer::print_remaining_time_with_pause(state).await;
+                    });
+                }
 
-        let _dbus_conn = self.start_dbus_service().await?;
+                daemon.set_current(Some(Current {
+                    session: session.clone(),
+                    task: self.config.task.clone(),
+                }));
+                self.run_dbus_actions(SessionEvent::Start, dbus_conn).await;
+                mpris = self
+                    .config
+                    .media
+                    .as_ref()
+                    .map(|_| MprisInterface::new(dbus_conn));
+                if let (
+                    Some(mpris),
+                    Some(MediaConfig {
+                        player: Some(player),
+                        uri,
+                        ..
+                    }),
+                ) = (&mpris, &self.config.media)
+                {
+                    if let Err(e) = mpris.start(player, uri.as_deref()).await {
+                        eprintln!("Starting media player {player} failed: {e}");
+                    }
+                }
 
-        let mut timer_aborted: Option<Signal> = None;
+                auto_pause_task = match (&self.config.auto_pause, &system_conn) {
+                    (Some(auto_pause), Some(system_conn)) => {
+                        Some(auto_pause.start(system_conn.clone(), session.clone()))
+                    }
+                    _ => None,
+                };
 
-        loop {
-            let (new_tx, new_rx) = oneshot::channel();
-            *self.tx.lock().unwrap() = Some(new_tx);
+                count_down(&state_tx, &mut commands, async |paused| {
+                    self.pause_changed(paused, dbus_conn, &mut mpris).await
+                })
+                .await
+            }
+            Err(e) => {
+                eprintln!("Starting the session failed, cleaning up: {e:#}");
+                Err(e)
+            }
+        };
+        let reason = *result.as_ref().unwrap_or(&FinishReason::Aborted);
+        // Commands that arrive during the cleanup are rejected
+        drop(commands);
+        // Make sure the cursor is shown. Should not be a problem if it was not disabled.
+        print!("\x1B[?25h"); // Show cursor
 
-            let current_duration = {
-                let timer = self.timer.lock().unwrap();
-                timer.remaining()
-            };
+        if let Some(guard_task) = applied.guard_task.take() {
+            guard_task.abort();
+        }
+        if let Some(auto_pause_task) = auto_pause_task {
+            auto_pause_task.abort();
+        }
 
-            if current_duration.is_zero() {
-                break;
+        // Restore the tools and notify the user. Every step is attempted, even if an earlier one
+        // failed, so that as much as possible is restored.
+        let mut report = CleanupReport::default();
+        report.step(
+            "Finish the session",
+            change_phase(&state_tx, Phase::Finishing),
+        );
+        if started {
+            for command in &self.config.sway_commands.on_end {
+                report.step(
+                    "Run sway command",
+                    sway.run_command(command)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("`{command}`: {e}")),
+                );
+            }
+        }
+        if applied.dnd {
+            report.step("Disable do not disturb", swaync.disable_dnd().await);
+        }
+        // Closing the lock releases it
+        drop(inhibit_lock);
+        if started {
+            report.step(
+                "Run D-Bus actions",
+                self.config
+                    .dbus_actions
+                    .run(SessionEvent::End, dbus_conn)
+                    .await,
+            );
+        }
+        if let (Some(mpris), Some(media)) = (&mut mpris, &self.config.media) {
+            if media.pause_on_end.unwrap_or(true) {
+                report.step("Pause media players", mpris.pause_playing().await);
             }
+        }
 
-            tokio::select! {
-                _ = sleep(current_duration) => {
-                    break;
-                },
-                _ = tokio::signal::ctrl_c() => {
-                    let timer = self.timer.lock().unwrap();
-                    timer_aborted = Some(Signal::CtrlC);
-                    println!("\x1B[2K\rFocus timer aborted at: {}", *timer);
-                    debug!("\nReceived Ctrl+C, starting cleanup...");
-                    break;
-                },
-                signal = new_rx => {
-                    match signal {
-                        Ok(Signal::Dbus) => {
-                            timer_aborted = Some(Signal::Dbus);
-                            debug!("\nReceived D-Bus stop signal, starting cleanup...");
-                            break;
-                        },
-                        Ok(Signal::TogglePause) => {
-                            let mut timer = self.timer.lock().unwrap();
-                            timer.toggle_pause();
-                            debug!("Timer pause toggled: paused = {}", timer.is_paused());
-                        },
-                        Ok(Signal::CtrlC) => {
-                            // This case should not happen, as we handle Ctrl+C above.
-                            break;
-                        },
-                        Err(_) => {
-                            debug!("\nReceived error from channel, starting cleanup...");
-                            break;
-                        },
-                    }
-                },
+        if applied.status_commands {
+            for (bar_id, command) in &status_commands {
+                report.step(
+                    "Restore bar status command",
+                    sway.set_status_command(bar_id, command.as_deref()).await,
+                );
             }
         }
-        // Make sure the cursor is shown. Should not be a problem if it was not disabled.
-        print!("\x1B[?25h"); // Show cursor
+        if applied.bars_invisible {
+            report.step("Restore bar mode", sway.restore_bar_mode(bar_modes).await);
+        }
+        for bar in bars.iter_mut() {
+            report.step("Show external bar", bar.show().await);
+        }
 
-        // Restore the tools and notify the user
-        swaync.disable_dnd().await?;
+        let processes_resumed = report.step(
+            "Continue stopped processes",
+            processes::resume(&applied.stopped_processes),
+        );
+        let outputs_restored = report.step(
+            "Restore outputs",
+            outputs::restore(&mut sway, &applied.output_states).await,
+        );
+        if let Some(bindings_session) = applied.bindings_session {
+            report.step(
+                "Restore key bindings",
+                bindings_session.restore(&mut sway).await,
+            );
+        }
+        if let Some(visual_session) = applied.visual_session {
+            report.step("Revert visual cues", visual_session.revert(&mut sway).await);
+        }
+        if let Some(stash_session) = applied.stash_session {
+            report.step(
+                "Restore stashed windows",
+                stash_session.restore(&mut sway).await,
+            );
+        }
+        if let (true, Some(workspace)) = (applied.workspace, &previous_workspace) {
+            report.step(
+                "Switch back to the previous workspace",
+                sway.switch_workspace(workspace).await,
+            );
+        }
+        // Keep the recovery state if the outputs are still off or processes are still stopped, so
+        // that they can be recovered
+        if outputs_restored && processes_resumed {
+            report.step("Clear recovery state", recovery::clear());
+        }
 
-        if !self.config.keep_status_bar {
-            sway.restore_bar_mode(bar_modes).await?;
+        if self.should_notify(reason) {
+            let state = session.state();
+            let mut body = format!("{}", state.timer);
+            if self.config.guard.is_some() {
+                let interruptions = state.interruptions;
+                body.push_str(&format!("\nInterruptions: {interruptions}"));
+            }
+            if let Some(overlap) = &overlap {
+                body.push_str(&format!("\n{overlap}"));
+            }
+            report.step(
+                "Send notification",
+                send_notification("Focus time over", &body).await,
+            );
         }
 
-        let mut hints = HashMap::new();
-        hints.insert("urgency", &Value::U8(2));
+        let finished = change_phase(&state_tx, Phase::Finished(reason));
+        let cleanup = report.finish().and(finished);
+        let reason = result?;
+        cleanup?;
+        Ok(reason)
+    }
 
-        if timer_aborted == Some(Signal::Dbus)
-            || (!self.config.no_notification && timer_aborted.is_none())
-        {
-            let notify = NotificationInterface::new().await?;
-            let _ = notify
-                .notify(
-                    "Focus time over",
-                    &format!("{}", *self.timer.lock().unwrap()),
-                    hints,
-                )
-                .await?;
+    /// Sets the desktop up for the session. Every change is recorded in `applied` before it is
+    /// made, so that the cleanup also undoes the changes of a setup which failed halfway.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The connection to sway.
+    /// * `swaync` - The interface to the notification daemon.
+    /// * `session` - The handle of the session, for the distraction guard.
+    /// * `status_commands` - The status commands of the sway bars.
+    /// * `bars` - The external bars to hide.
+    /// * `applied` - Records the changes made to the desktop.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the setup.
+    async fn set_up(
+        &self,
+        sway: &mut SwayIpcInterface,
+        swaync: &SwayNCInterface,
+        session: &SessionHandle,
+        status_commands: &[(String, Option<String>)],
+        bars: &mut [ExternalBar],
+        applied: &mut Applied,
+    ) -> Result<()> {
+        applied.dnd = true;
+        swaync.enable_dnd().await?;
+        if !self.config.keep_status_bar {
+            if self.config.focus_bar {
+                let exe = std::env::current_exe()?;
+                let command = format!("{} bar", shell_quote(&exe.display().to_string()));
+                applied.status_commands = true;
+                for (bar_id, _) in status_commands {
+                    sway.set_status_command(bar_id, Some(&command)).await?;
+                }
+            } else {
+                applied.bars_invisible = true;
+                sway.set_bars_invisible().await?;
+            }
+            for bar in bars.iter_mut() {
+                bar.hide().await?;
+            }
+        }
+        let output_states = match &self.config.outputs {
+            Some(outputs) => outputs.capture(sway).await?,
+            None => Vec::new(),
+        };
+        let stopped_processes = match &self.config.processes {
+            Some(processes) => processes.find()?,
+            None => Vec::new(),
+        };
+        recovery::save(&RecoveryState {
+            outputs: output_states.clone(),
+            stopped_processes: stopped_processes.clone(),
+        })?;
+        applied.output_states = output_states;
+        if let Some(outputs) = &self.config.outputs {
+            outputs.apply(sway, &applied.output_states).await?;
+        }
+        applied.stopped_processes = stopped_processes;
+        processes::stop(&applied.stopped_processes)?;
+        if let Some(stash) = &self.config.stash {
+            applied.stash_session = Some(stash.start(sway).await?);
+        }
+        applied.guard_task = self
+            .config
+            .guard
+            .as_ref()
+            .map(|guard| guard.start(session.clone()));
+        if let Some(workspace) = &self.config.workspace {
+            applied.workspace = true;
+            // Moving fails if the focused workspace is empty
+            if self.config.move_window && sway.get_focused_window().await?.is_some() {
+                sway.move_focused_to_workspace(workspace).await?;
+            }
+            sway.switch_workspace(workspace).await?;
+        }
+        if let Some(visual) = &self.config.visual {
+            applied.visual_session = Some(visual.apply(sway).await?);
+        }
+        if let Some(bindings) = &self.config.bindings {
+            applied.bindings_session = Some(bindings.apply(sway).await?);
+        }
+        for command in &self.config.sway_commands.on_start {
+            if let Err(e) = sway.run_command(command).await {
+                eprintln!("Sway command `{command}` failed: {e}");
+            }
         }
-
         Ok(())
     }
 
-    /// Starts a D-Bus service that provides a FocusTime interface.
-    /// This interface can be used to query the remaining time of the focus timer.
-    /// The service is registered under the name `org.towoe.FocusTime`.
-    /// The interface provides a method `get_remaining_time` that returns the remaining time in HH:MM:SS format.
+    /// Runs a named timer next to the focus session. Only the timer runs, nothing on the desktop
+    /// is changed, and a notification is sent at the end.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the timer, shown in the notification.
+    /// * `daemon` - The daemon, which gives D-Bus clients access to the timer.
     ///
     /// # Returns
     ///
-    /// A `Result` containing the D-Bus connection to the service or an error.
+    /// A `Result` containing the reason why the timer finished.
+    pub async fn run_timer(&self, name: &str, daemon: &Daemon) -> Result<FinishReason> {
+        let state = SessionState::new(Timer::new(self.config.duration));
+        let (session, mut commands, state_tx) = SessionHandle::new(state);
+        if self.config.print_time {
+            let state = session.subscribe();
+            tokio::spawn(async move {
+                crate::timer::print_remaining_time_with_pause(state).await;
+            });
+        }
+        daemon.set_current(Some(Current {
+            session: session.clone(),
+            task: self.config.task.clone(),
+        }));
+
+        let reason = count_down(&state_tx, &mut commands, async |_| {}).await?;
+        drop(commands);
+        change_phase(&state_tx, Phase::Finishing)?;
+        print!("\x1B[?25h"); // Show cursor
+
+        let notified = if self.should_notify(reason) {
+            let mut body = format!("{}", session.state().timer);
+            if let Some(task) = &self.config.task {
+                body.push_str(&format!("\n{task}"));
+            }
+            send_notification(&format!("Timer {name} over"), &body).await
+        } else {
+            Ok(())
+        };
+        change_phase(&state_tx, Phase::Finished(reason))?;
+        notified?;
+        Ok(reason)
+    }
+
+    /// Checks whether the session ends too close to the next commitment in the calendar.
+    fn overlap(&self) -> Option<Overlap> {
+        let calendar = self.config.calendar.as_ref()?;
+        let overlap = calendar.overlap(Utc::now(), self.config.duration);
+        if let Some(overlap) = &overlap {
+            info!("Next commitment: {overlap:?}");
+        }
+        overlap
+    }
+
+    /// Checks whether the user is notified about the end of the session. A stopped session is
+    /// always notified about, a completed one unless disabled.
+    fn should_notify(&self, reason: FinishReason) -> bool {
+        reason == FinishReason::Stopped
+            || (!self.config.no_notification && reason == FinishReason::Completed)
+    }
+
+    /// Runs the actions for a change of the pause state of the timer. Failures are only reported.
     ///
-    /// # Client example
+    /// # Arguments
     ///
-    /// $ busctl --user call org.towoe.FocusTime /org/towoe/FocusTime org.towoe.FocusTime GetRemainingTime
-    async fn start_dbus_service(&self) -> Result<Connection> {
-        debug!("Starting D-Bus service");
-        let conn = Connection::session().await?;
-        let tx = Arc::clone(&self.tx);
-        conn.object_server()
-            .at(
-                "/org/towoe/FocusTime",
-                FocusTime {
-                    timer: Arc::clone(&self.timer),
-                    tx,
-                },
-            )
-            .await?;
-        conn.request_name("org.towoe.FocusTime").await?;
-        Ok(conn)
+    /// * `paused` - Whether the timer is now paused.
+    /// * `connection` - The connection to the session bus.
+    /// * `mpris` - The interface to the media players, if they are controlled.
+    async fn pause_changed(
+        &self,
+        paused: bool,
+        connection: &Connection,
+        mpris: &mut Option<MprisInterface>,
+    ) {
+        let event = if paused {
+            SessionEvent::Pause
+        } else {
+            SessionEvent::Resume
+        };
+        self.run_dbus_actions(event, connection).await;
+        if let (Some(mpris), Some(media)) = (mpris, &self.config.media) {
+            if media.pause_on_break.unwrap_or(true) {
+                if !paused {
+                    mpris.resume_paused().await;
+                } else if let Err(e) = mpris.pause_playing().await {
+                    eprintln!("Controlling media players failed: {e}");
+                }
+            }
+        }
+    }
+
+    /// Makes the D-Bus method calls configured for a session event. A failing call does not
+    /// interrupt the session, it is only reported.
+    async fn run_dbus_actions(&self, event: SessionEvent, connection: &Connection) {
+        if let Err(e) = self.config.dbus_actions.run(event, connection).await {
+            eprintln!("{e:#}");
+        }
     }
 }
 
@@ -316,6 +917,14 @@ impl Focus {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("/home/me/it's mine/focus-time"),
+            r"'/home/me/it'\''s mine/focus-time'"
+        );
+    }
+
     #[test]
     fn test_parse_duration_valid() {
         assert_eq!(parse_duration("50s"), Some(Duration::from_secs(50)));
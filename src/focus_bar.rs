@@ -0,0 +1,67 @@
+/// This module implements the `focus-time bar` status command, which replaces the contents of the
+/// sway bars during a focus session. It prints the status of the running focus timer using the
+/// i3bar protocol whenever the daemon signals a change, and once per second while the timer counts
+/// down.
+use crate::client::{self, FocusTimerProxy};
+
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::io::Write;
+use std::time::Duration;
+use zbus::Connection;
+
+/// The header of the i3bar protocol.
+#[derive(Serialize)]
+struct Header {
+    version: u32,
+}
+
+/// A block of the status line in the i3bar protocol.
+#[derive(Serialize)]
+struct Block {
+    name: &'static str,
+    full_text: String,
+}
+
+/// Prints the status of the focus timer until the bar closes the output.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation. Once the bar stops reading,
+/// writing to stdout fails and the error is returned.
+pub async fn run() -> Result<()> {
+    let connection = Connection::session().await?;
+    let proxy = FocusTimerProxy::new(&connection).await?;
+
+    let mut changes = proxy.receive_state_changed().await?;
+    // The daemon starting or exiting
+    let mut owner = proxy.inner().receive_owner_changed().await?;
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", serde_json::to_string(&Header { version: 1 })?)?;
+    // The status line is an infinite array of block arrays
+    writeln!(stdout, "[")?;
+    loop {
+        let state = proxy.get_state().await.ok();
+        // Between sessions there is no timer to query, show an empty status line then
+        let blocks = match client::status_text(&proxy).await {
+            Ok(status) if status != "idle" => vec![Block {
+                name: "focus-time",
+                full_text: status,
+            }],
+            _ => Vec::new(),
+        };
+        writeln!(stdout, "{},", serde_json::to_string(&blocks)?)?;
+        stdout.flush()?;
+
+        // Only the remaining time changes without a signal, while the timer counts down
+        let counting = matches!(state.as_deref(), Some("running" | "overtime"));
+        tokio::select! {
+            Some(_) = changes.next() => {},
+            Some(_) = owner.next() => {},
+            _ = tokio::time::sleep(Duration::from_secs(1)), if counting => {},
+            else => return Err(anyhow!("The connection to the session bus was closed")),
+        }
+    }
+}
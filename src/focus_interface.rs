@@ -1,16 +1,18 @@
-use std::sync::{Arc, Mutex};
-use tokio::sync::oneshot;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zbus::fdo;
 use zbus::interface;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedValue;
 
-use crate::focus::Signal;
-use crate::timer::Timer;
+use crate::daemon::{self, Current, Daemon};
+use crate::session::Command;
 
-/// Represents the focus time with a timer and a channel for abort signals.
+/// Represents the focus time, which gives access to the daemon and its running focus session.
 pub struct FocusTime {
-    /// The timer for the focus session.
-    pub timer: Arc<Mutex<Timer>>,
-    /// A thread-safe optional sender for abort signals.
-    pub tx: Arc<Mutex<Option<oneshot::Sender<Signal>>>>,
+    /// The daemon running the focus sessions.
+    pub daemon: Arc<Daemon>,
 }
 
 #[interface(name = "org.towoe.FocusTime")]
@@ -19,39 +21,208 @@ impl FocusTime {
     ///
     /// # Returns
     ///
-    /// A `String` representing the remaining time in a fixed format.
-    pub async fn get_remaining_time(&self) -> String {
-        let timer = self.timer.lock().unwrap();
-        timer.remaining_str_fixed_format()
+    /// A `String` representing the remaining time in a fixed format, or an error while idle.
+    pub async fn get_remaining_time(&self) -> fdo::Result<String> {
+        Ok(self
+            .current()?
+            .session
+            .state()
+            .timer
+            .remaining_str_fixed_format())
     }
 
     /// Retrieves the current pause state of the timer.
     ///
     /// # Returns
     ///
-    /// A `bool` indicating whether the timer is paused.
-    pub async fn get_paused(&self) -> bool {
-        let timer = self.timer.lock().unwrap();
-        timer.is_paused()
+    /// A `bool` indicating whether the timer is paused, or an error while idle.
+    pub async fn get_paused(&self) -> fdo::Result<bool> {
+        Ok(self.current()?.session.state().timer.is_paused())
     }
 
-    /// Stops the focus timer by sending an abort signal.
+    /// Retrieves the number of times a blocked window got focus during the session.
     ///
-    /// This method locks the mutex, takes the sender if available, and sends an `AbortSignal::Dbus`.
-    pub async fn stop_timer(&self) {
-        let mut tx_lock = self.tx.lock().unwrap();
-        if let Some(tx) = tx_lock.take() {
-            let _ = tx.send(Signal::Dbus);
+    /// # Returns
+    ///
+    /// A `u32` with the number of interruptions, or an error while idle.
+    pub async fn get_interruptions(&self) -> fdo::Result<u32> {
+        Ok(self.current()?.session.state().interruptions)
+    }
+
+    /// Retrieves the task of the focus session.
+    ///
+    /// # Returns
+    ///
+    /// A `String` with the task, empty if no task was given, or an error while idle.
+    pub async fn get_task(&self) -> fdo::Result<String> {
+        Ok(self.current()?.task.unwrap_or_default())
+    }
+
+    /// Retrieves the phase of the focus session.
+    ///
+    /// # Returns
+    ///
+    /// A `String` with the name of the phase, e.g. `running`, `paused` or `break`, and `idle` if
+    /// no session is running.
+    pub async fn get_state(&self) -> String {
+        match self.daemon.current() {
+            Some(current) => current.session.state().phase.name().to_string(),
+            None => "idle".to_string(),
         }
     }
 
+    /// Retrieves every phase change of the focus session so far.
+    ///
+    /// # Returns
+    ///
+    /// A list of the changes, each with the time in milliseconds since the Unix epoch and the
+    /// phase, e.g. `finished (stopped)`. The list is empty while idle.
+    pub async fn get_transitions(&self) -> Vec<(u64, String)> {
+        let Some(current) = self.daemon.current() else {
+            return Vec::new();
+        };
+        current
+            .session
+            .state()
+            .transitions
+            .iter()
+            .map(|transition| (epoch_millis(transition.time), transition.phase.to_string()))
+            .collect()
+    }
+
+    /// Retrieves the number of sessions waiting to be started.
+    ///
+    /// # Returns
+    ///
+    /// A `u32` with the number of queued sessions.
+    pub async fn get_queue_length(&self) -> u32 {
+        self.daemon.queue_len() as u32
+    }
+
+    /// Starts a focus session, or queues it if a session is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The duration, e.g. `25m`, or empty for the configured duration.
+    /// * `options` - The session options, e.g. `task` or `focus-bar`.
+    ///
+    /// # Returns
+    ///
+    /// The number of sessions before this one, `0` if it starts right away.
+    pub async fn start(
+        &self,
+        duration: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<u32> {
+        daemon::from_options(&duration, &options)
+            .and_then(|args| self.daemon.enqueue(args))
+            .map(|position| position as u32)
+            .map_err(|e| fdo::Error::InvalidArgs(format!("{e:#}")))
+    }
+
+    /// Schedules a focus session once, at the next occurrence of a time of the day.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of the day, e.g. `09:00`.
+    /// * `duration` - The duration, e.g. `25m`, or empty for the configured duration.
+    /// * `options` - The session options, see [`FocusTime::start`].
+    ///
+    /// # Returns
+    ///
+    /// The id of the scheduled session, and its start in milliseconds since the Unix epoch.
+    pub async fn schedule(
+        &self,
+        time: String,
+        duration: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<(u32, u64)> {
+        daemon::from_options(&duration, &options)
+            .and_then(|args| self.daemon.schedule(&time, args))
+            .map(|(id, start)| (id, epoch_millis(start.into())))
+            .map_err(|e| fdo::Error::InvalidArgs(format!("{e:#}")))
+    }
+
+    /// Retrieves the scheduled sessions.
+    ///
+    /// # Returns
+    ///
+    /// A list of the sessions ordered by their next start, each with the id, the next start in
+    /// milliseconds since the Unix epoch, whether it is configured in the configuration file, and
+    /// a description, e.g. `Mon,Fri at 09:00, 50m - Deep work`.
+    pub async fn get_schedule(&self) -> Vec<(u32, u64, bool, String)> {
+        self.daemon
+            .scheduled()
+            .into_iter()
+            .map(|scheduled| {
+                let next = scheduled.next.map(SystemTime::from).unwrap_or(UNIX_EPOCH);
+                (
+                    scheduled.id,
+                    epoch_millis(next),
+                    scheduled.from_config,
+                    scheduled.entry.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    /// Cancels a session scheduled with [`FocusTime::schedule`].
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the session.
+    pub async fn cancel_scheduled(&self, id: u32) -> fdo::Result<()> {
+        self.daemon
+            .cancel_scheduled(id)
+            .map_err(|e| fdo::Error::InvalidArgs(format!("{e:#}")))
+    }
+
+    /// Stops the focus timer.
+    ///
+    /// This method sends a stop command to the session and waits until it was received.
+    pub async fn stop_timer(&self) -> fdo::Result<()> {
+        self.send(Command::Stop).await
+    }
+
     /// Toggles the timer between paused and running states.
     ///
-    /// This method sends a toggle pause signal through the channel.
-    pub async fn toggle_pause(&self) {
-        let mut tx_lock = self.tx.lock().unwrap();
-        if let Some(tx) = tx_lock.take() {
-            let _ = tx.send(Signal::TogglePause);
-        }
+    /// This method sends a toggle pause command to the session and waits until it was applied.
+    pub async fn toggle_pause(&self) -> fdo::Result<()> {
+        self.send(Command::TogglePause).await
+    }
+
+    /// Emitted when a session starts or ends, and when the running session changes its phase or
+    /// its number of interruptions.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The phase of the session, see [`FocusTime::get_state`].
+    #[zbus(signal)]
+    pub async fn state_changed(emitter: &SignalEmitter<'_>, state: &str) -> zbus::Result<()>;
+}
+
+/// Converts a time into milliseconds since the Unix epoch, as used on D-Bus.
+fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl FocusTime {
+    /// Returns the running session, or an error for D-Bus while idle.
+    fn current(&self) -> fdo::Result<Current> {
+        self.daemon
+            .current()
+            .ok_or_else(|| fdo::Error::Failed("No focus session is running".to_string()))
+    }
+
+    /// Sends a command to the session, converting the error for D-Bus.
+    async fn send(&self, command: Command) -> fdo::Result<()> {
+        self.current()?
+            .session
+            .send(command)
+            .await
+            .map(|_| ())
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
     }
 }
@@ -0,0 +1,321 @@
+use crate::config::{GuardAction, GuardConfig};
+use crate::notification_interface::NotificationInterface;
+use crate::session::{Command, SessionHandle};
+use crate::sway_ipc_interface::{self, SwayIpcInterface, WindowInfo};
+use crate::window_rule::{self, WindowRule};
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use log::{debug, warn};
+use std::collections::HashMap;
+use swayipc_async::{Event, WindowChange};
+use tokio::task::JoinHandle;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+/// Watches the window focus during a session and reacts when a blocked window gets focus.
+#[derive(Clone, Debug)]
+pub struct Guard {
+    rules: Vec<WindowRule>,
+    action: GuardAction,
+}
+
+impl Guard {
+    /// Creates a new `Guard` from its configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The guard configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Guard`, or an error if a rule is invalid.
+    pub fn from_config(config: &GuardConfig) -> Result<Self> {
+        let rules = config
+            .rule
+            .iter()
+            .map(WindowRule::from_config)
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rules,
+            action: config.action.unwrap_or_default(),
+        })
+    }
+
+    /// Starts watching the window focus in the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session, which counts each time a blocked window gets focus.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the background task, which has to be aborted at the end of the session.
+    pub fn start(&self, session: SessionHandle) -> JoinHandle<()> {
+        let guard = self.clone();
+        sway_ipc_interface::spawn_watcher("window focus", move || {
+            let guard = guard.clone();
+            let session = session.clone();
+            async move { guard.watch_focus(session).await }
+        })
+    }
+
+    async fn watch_focus(&self, session: SessionHandle) -> Result<()> {
+        let mut events = SwayIpcInterface::subscribe_window_events().await?;
+        let mut sway = SwayIpcInterface::new().await?;
+        let connection = Connection::session().await?;
+        let mut previous: Option<i64> = None;
+        while let Some(event) = events.next().await {
+            let Event::Window(event) = event? else {
+                continue;
+            };
+            if event.change != WindowChange::Focus {
+                continue;
+            }
+            let Some(window) = sway.get_window(event.container.id).await? else {
+                continue;
+            };
+            if let Some(id) = self
+                .on_focus(&window, &mut previous, &session, &connection)
+                .await?
+            {
+                if let Err(e) = sway.focus_window(id).await {
+                    warn!("Failed to focus the previous window again: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reacts to a window getting focus. A blocked window is counted as an interruption, and a
+    /// failing notification is only logged, so that the guard keeps watching.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The focused window.
+    /// * `previous` - The last focused window which is not blocked, updated for other windows.
+    /// * `session` - The session, which counts the interruptions.
+    /// * `connection` - The connection to the session bus, for notifications.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the window to focus instead of a blocked one, or an error if the
+    /// session has ended.
+    async fn on_focus(
+        &self,
+        window: &WindowInfo,
+        previous: &mut Option<i64>,
+        session: &SessionHandle,
+        connection: &Connection,
+    ) -> Result<Option<i64>> {
+        if !window_rule::any_matches(&self.rules, window) {
+            *previous = Some(window.id);
+            return Ok(None);
+        }
+
+        let count = session
+            .send(Command::RecordInterruption)
+            .await?
+            .interruptions;
+        debug!("Blocked window focused ({count} interruptions): {window:?}");
+        match self.action {
+            GuardAction::Record => {}
+            GuardAction::Notify => {
+                if let Err(e) = notify(window, connection).await {
+                    warn!("Failed to send the guard notification: {e}");
+                }
+            }
+            GuardAction::Refocus => return Ok(*previous),
+        }
+        Ok(None)
+    }
+}
+
+/// Sends a gentle reminder that the focused window is blocked.
+async fn notify(window: &WindowInfo, connection: &Connection) -> Result<()> {
+    let name = window
+        .title
+        .as_deref()
+        .or(window.app_id.as_deref())
+        .or(window.class.as_deref())
+        .unwrap_or("This window");
+    let mut hints = HashMap::new();
+    hints.insert("urgency", &Value::U8(2));
+    let notify = NotificationInterface::from_connection(connection).await?;
+    notify
+        .notify(
+            "Stay focused",
+            &format!("{name} is blocked during the focus session"),
+            hints,
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionState;
+    use crate::timer::Timer;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use zbus::zvariant::OwnedValue;
+
+    /// The bodies of the received notifications with their urgency.
+    type Bodies = Arc<Mutex<Vec<(String, Option<u8>)>>>;
+
+    /// A stand-in notification daemon, which records the bodies with their urgency or fails
+    /// every notification.
+    struct Notifications {
+        bodies: Bodies,
+        fail: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl Notifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            _summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> zbus::fdo::Result<u32> {
+            if self.fail {
+                return Err(zbus::fdo::Error::Failed("No notifications".to_string()));
+            }
+            let urgency = hints.get("urgency").and_then(|v| u8::try_from(v).ok());
+            self.bodies.lock().unwrap().push((body, urgency));
+            Ok(1)
+        }
+    }
+
+    async fn connection(notifications: Notifications) -> (Connection, Connection) {
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        tokio::try_join!(
+            zbus::connection::Builder::unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/Notifications", notifications)
+                .unwrap()
+                .build(),
+            zbus::connection::Builder::unix_stream(client).p2p().build(),
+        )
+        .unwrap()
+    }
+
+    /// Returns a session which applies the commands it receives.
+    fn session() -> SessionHandle {
+        let mut state = SessionState::new(Timer::new(Duration::from_secs(60)));
+        let (session, mut commands, _state_tx) = SessionHandle::new(state.clone());
+        tokio::spawn(async move {
+            while let Some((command, reply)) = commands.recv().await {
+                let result = state.apply(command).map(|_| state.clone());
+                let _ = reply.send(result);
+            }
+        });
+        session
+    }
+
+    fn guard(action: &str) -> Guard {
+        let config: GuardConfig = toml::from_str(&format!(
+            "action = \"{action}\"\n[[rule]]\napp-id = \"^firefox$\""
+        ))
+        .unwrap();
+        Guard::from_config(&config).unwrap()
+    }
+
+    fn window(id: i64, app_id: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            workspace: "1".to_string(),
+            floating: false,
+            focused: true,
+            fullscreen: false,
+            app_id: Some(app_id.to_string()),
+            class: None,
+            title: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let notifications = Notifications {
+            bodies: Arc::clone(&bodies),
+            fail: false,
+        };
+        let (_server, client) = connection(notifications).await;
+        let session = session();
+        let guard = guard("notify");
+        let mut previous = None;
+
+        let editor = window(1, "foot");
+        let refocus = guard.on_focus(&editor, &mut previous, &session, &client);
+        assert_eq!(refocus.await.unwrap(), None);
+        assert_eq!(previous, Some(1));
+
+        let browser = window(2, "firefox");
+        let refocus = guard.on_focus(&browser, &mut previous, &session, &client);
+        assert_eq!(refocus.await.unwrap(), None);
+        assert_eq!(previous, Some(1));
+        assert_eq!(
+            *bodies.lock().unwrap(),
+            vec![(
+                "firefox is blocked during the focus session".to_string(),
+                Some(2)
+            )]
+        );
+        assert_eq!(session.send(Command::Stop).await.unwrap().interruptions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_notification() {
+        let notifications = Notifications {
+            bodies: Arc::new(Mutex::new(Vec::new())),
+            fail: true,
+        };
+        let (_server, client) = connection(notifications).await;
+        let session = session();
+        let guard = guard("notify");
+
+        // The guard keeps watching after a notification failed
+        let browser = window(2, "firefox");
+        let mut previous = None;
+        for _ in 0..2 {
+            let refocus = guard.on_focus(&browser, &mut previous, &session, &client);
+            assert_eq!(refocus.await.unwrap(), None);
+        }
+        assert_eq!(session.send(Command::Stop).await.unwrap().interruptions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_refocus() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let notifications = Notifications {
+            bodies: Arc::clone(&bodies),
+            fail: false,
+        };
+        let (_server, client) = connection(notifications).await;
+        let session = session();
+        let guard = guard("refocus");
+        let mut previous = None;
+
+        // Without a previous window there is nothing to go back to
+        let browser = window(2, "firefox");
+        let refocus = guard.on_focus(&browser, &mut previous, &session, &client);
+        assert_eq!(refocus.await.unwrap(), None);
+        let editor = window(1, "foot");
+        let refocus = guard.on_focus(&editor, &mut previous, &session, &client);
+        assert_eq!(refocus.await.unwrap(), None);
+        let refocus = guard.on_focus(&browser, &mut previous, &session, &client);
+        assert_eq!(refocus.await.unwrap(), Some(1));
+        assert!(bodies.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::InhibitWhat;
+use crate::logind::LoginManagerProxy;
+
+use log::debug;
+use zbus::zvariant::OwnedFd;
+use zbus::{Connection, Result};
+
+/// Operations which are inhibited during the focus session, e.g. the screen locking when idle.
+#[derive(Clone, Debug)]
+pub struct Inhibit {
+    what: Vec<InhibitWhat>,
+}
+
+/// An inhibitor lock held by logind. The lock is released when it is dropped.
+#[derive(Debug)]
+pub struct InhibitLock {
+    _fd: OwnedFd,
+}
+
+impl Inhibit {
+    /// Creates a new `Inhibit` for the given operations.
+    pub fn new(what: Vec<InhibitWhat>) -> Self {
+        Self { what }
+    }
+
+    /// Takes an inhibitor lock from logind.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection to the bus of logind, usually the system bus.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the lock, which has to be kept for the duration of the session.
+    pub async fn acquire(&self, connection: &Connection) -> Result<InhibitLock> {
+        let what = self
+            .what
+            .iter()
+            .map(|what| what.as_str())
+            .collect::<Vec<_>>()
+            .join(":");
+        debug!("Taking inhibitor lock for {what}");
+        let manager = LoginManagerProxy::new(connection).await?;
+        let fd = manager
+            .inhibit(&what, "focus-time", "Focus session running", "block")
+            .await?;
+        Ok(InhibitLock { _fd: fd })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    /// A stand-in logind, which hands out the write end of a pipe as the lock.
+    struct Manager {
+        what: Arc<Mutex<Option<String>>>,
+        lock: Mutex<Option<std::io::PipeWriter>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl Manager {
+        fn inhibit(&self, what: String, _who: String, _why: String, mode: String) -> OwnedFd {
+            assert_eq!(mode, "block");
+            *self.what.lock().unwrap() = Some(what);
+            let lock = self.lock.lock().unwrap().take().unwrap();
+            OwnedFd::from(std::os::fd::OwnedFd::from(lock))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acquire_and_release() {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let what = Arc::new(Mutex::new(None));
+        let manager = Manager {
+            what: Arc::clone(&what),
+            lock: Mutex::new(Some(writer)),
+        };
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let (_server, client) = tokio::try_join!(
+            zbus::connection::Builder::unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/login1", manager)
+                .unwrap()
+                .build(),
+            zbus::connection::Builder::unix_stream(client).p2p().build(),
+        )
+        .unwrap();
+
+        let inhibit = Inhibit::new(vec![InhibitWhat::Idle, InhibitWhat::Sleep]);
+        let lock = inhibit.acquire(&client).await.unwrap();
+        assert_eq!(what.lock().unwrap().as_deref(), Some("idle:sleep"));
+
+        // The pipe is closed once the only remaining write end, the lock, is dropped
+        drop(lock);
+        let mut content = Vec::new();
+        assert_eq!(reader.read_to_end(&mut content).unwrap(), 0);
+    }
+}
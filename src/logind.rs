@@ -0,0 +1,58 @@
+use zbus::{proxy, zvariant::OwnedFd, Result};
+
+/// Proxy interface for the login manager of systemd-logind.
+///
+/// The service is identified by the interface `org.freedesktop.login1.Manager`, the default
+/// service name `org.freedesktop.login1`, and the default object path `/org/freedesktop/login1`.
+/// It is available on the system bus.
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+pub trait LoginManager {
+    /// Takes an inhibitor lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `what` - The operations to inhibit, separated by colons, e.g. `idle:sleep`.
+    /// * `who` - The name of the application taking the lock.
+    /// * `why` - The reason for taking the lock.
+    /// * `mode` - Either `block` or `delay`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the file descriptor of the lock. The lock is released when the file
+    /// descriptor is closed.
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> Result<OwnedFd>;
+}
+
+/// Proxy interface for the login session of the user.
+///
+/// The default object path `/org/freedesktop/login1/session/auto` refers to the session of the
+/// calling process, or to the graphical session of the user.
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+pub trait LoginSession {
+    /// Emitted when the session is asked to lock the screen, e.g. by `loginctl lock-session`.
+    /// The screen locker itself is started by whoever listens to it, like swayidle's `lock`
+    /// hook.
+    #[zbus(signal)]
+    fn lock(&self) -> Result<()>;
+
+    /// Emitted when the session is asked to unlock the screen.
+    #[zbus(signal)]
+    fn unlock(&self) -> Result<()>;
+
+    /// Whether the user is idle.
+    #[zbus(property)]
+    fn idle_hint(&self) -> Result<bool>;
+
+    /// Whether the screen is locked, as reported by the screen locker or swayidle. Unlike the
+    /// `Unlock` signal, this is also reset when the screen locker exits on its own.
+    #[zbus(property)]
+    fn locked_hint(&self) -> Result<bool>;
+}
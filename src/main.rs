@@ -1,17 +1,41 @@
+mod auto_pause;
+mod bar;
+mod bindings;
+mod calendar;
 mod cli;
 mod client;
 mod config;
+mod daemon;
+mod dbus_actions;
 mod focus;
+mod focus_bar;
 mod focus_interface;
+mod guard;
+mod inhibit;
+mod logind;
+mod mpris;
+mod mpris_interface;
 mod notification;
 mod notification_interface;
+mod outputs;
+mod process;
+mod processes;
+mod recovery;
+mod schedule;
+mod service;
+mod session;
+mod stash;
+mod sway_commands;
 mod sway_ipc_interface;
 mod swaync;
 mod swaync_interface;
 mod timer;
+mod visual;
+mod window_rule;
 
 use clap::{CommandFactory, Parser};
 use cli::{Cli, Commands};
+use daemon::OtherInstance;
 
 use env_logger::Env;
 use log::info;
@@ -22,8 +46,8 @@ use log::info;
 /// and starts the focus timer.
 /// The main logic is implemented in the [`focus`](crate::focus) module.
 /// The [`Cli`] struct is used to parse command line arguments.
-/// With the parsed arguments, a new focus timer is created with [`focus::new`](crate::focus::new).
-/// Finally, the focus timer is started with [`focus::Focus::run`](crate::focus::Focus::run).
+/// Without a subcommand, the focus timer is run by [`daemon::run`](crate::daemon::run), which
+/// also runs the sessions queued in the meantime.
 ///
 /// # Returns
 ///
@@ -46,18 +70,44 @@ async fn main() -> anyhow::Result<()> {
     // Log the start of the focus timer
     info!("Starting focus timer");
 
+    let other = if args.replace {
+        OtherInstance::Replace
+    } else if args.queue {
+        OtherInstance::Queue
+    } else {
+        OtherInstance::Refuse
+    };
+
     // Handle subcommands or start the focus timer
     if let Some(command) = args.command {
         match command {
             Commands::Completions { shell } => {
-                let mut cmd = cli::Cli::command();
+                let profiles = config::load_from_file(&args.config)
+                    .map(|config| config.profile_names())
+                    .unwrap_or_default();
+                let mut cmd = cli::with_profile_names(cli::Cli::command(), &profiles);
                 let name = cmd.get_name().to_string();
                 clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
             }
-            _ => client::handle_command(command).await?,
+            Commands::Daemon => daemon::run(args.config, args.name, None, true, other).await?,
+            Commands::Bar => focus_bar::run().await?,
+            Commands::Recover => {
+                if !recovery::recover().await? {
+                    println!("Nothing to recover.");
+                }
+            }
+            Commands::InstallService { check } => {
+                if check {
+                    service::check()?;
+                } else {
+                    service::install()?;
+                }
+            }
+            Commands::UninstallService => service::uninstall()?,
+            _ => client::handle_command(command, args.name).await?,
         }
     } else {
-        focus::new(args)?.run().await?;
+        daemon::run(args.config, args.name, Some(args.session), false, other).await?;
     }
 
     Ok(())
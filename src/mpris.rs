@@ -0,0 +1,40 @@
+use zbus::{proxy, Result};
+
+/// Proxy interface for controlling a media player over MPRIS.
+///
+/// Every media player registers its own bus name starting with `org.mpris.MediaPlayer2.`, so the
+/// proxy has no default service. The object path is `/org/mpris/MediaPlayer2` for all players.
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+pub trait MediaPlayer2Player {
+    /// Starts or resumes playback.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    fn play(&self) -> Result<()>;
+
+    /// Pauses playback.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    fn pause(&self) -> Result<()>;
+
+    /// Opens and plays the given URI, e.g. a playlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - The URI of the track or playlist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    fn open_uri(&self, uri: &str) -> Result<()>;
+
+    /// The current playback status, `Playing`, `Paused` or `Stopped`.
+    #[zbus(property)]
+    fn playback_status(&self) -> Result<String>;
+}
@@ -0,0 +1,230 @@
+// src/mpris_interface.rs
+use crate::mpris::MediaPlayer2PlayerProxy;
+
+use log::{debug, warn};
+use zbus::fdo::DBusProxy;
+use zbus::proxy::CacheProperties;
+use zbus::{Connection, Result};
+
+/// The common prefix of the bus names of all MPRIS media players.
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// Represents the interface to control the MPRIS media players on the session bus.
+pub struct MprisInterface {
+    /// Connection to the session bus.
+    connection: Connection,
+    /// Bus names of the players which were paused and have not been resumed yet.
+    paused: Vec<String>,
+}
+
+impl MprisInterface {
+    /// Creates a new instance of `MprisInterface`.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection to the session bus.
+    pub fn new(connection: &Connection) -> Self {
+        Self {
+            connection: connection.clone(),
+            paused: Vec::new(),
+        }
+    }
+
+    /// Lists the bus names of all media players on the session bus.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the bus names, e.g. `org.mpris.MediaPlayer2.spotify`.
+    pub async fn players(&self) -> Result<Vec<String>> {
+        let dbus = DBusProxy::new(&self.connection).await?;
+        Ok(dbus
+            .list_names()
+            .await?
+            .into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| name.starts_with(MPRIS_PREFIX))
+            .collect())
+    }
+
+    /// Creates a proxy for a media player. Properties are not cached, as they are only read once.
+    async fn player(&self, name: &str) -> Result<MediaPlayer2PlayerProxy<'static>> {
+        MediaPlayer2PlayerProxy::builder(&self.connection)
+            .destination(name.to_string())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+    }
+
+    /// Starts playback in a media player.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The bus name of the player, or the part after `org.mpris.MediaPlayer2.`.
+    /// * `uri` - An optional URI to play, e.g. a focus playlist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn start(&self, name: &str, uri: Option<&str>) -> Result<()> {
+        let name = if name.starts_with(MPRIS_PREFIX) {
+            name.to_string()
+        } else {
+            format!("{MPRIS_PREFIX}{name}")
+        };
+        let player = self.player(&name).await?;
+        debug!("Starting playback in {name}, URI: {uri:?}");
+        match uri {
+            Some(uri) => player.open_uri(uri).await,
+            None => player.play().await,
+        }
+    }
+
+    /// Pauses all players which are currently playing, and remembers them. A player which fails
+    /// is skipped and logged, so that it does not keep the other players playing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the players could be listed.
+    pub async fn pause_playing(&mut self) -> Result<()> {
+        for name in self.players().await? {
+            match self.pause_player(&name).await {
+                Ok(true) => self.paused.push(name),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to pause {name}: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pauses a player if it is playing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing whether the player was paused.
+    async fn pause_player(&self, name: &str) -> Result<bool> {
+        let player = self.player(name).await?;
+        if player.playback_status().await? != "Playing" {
+            return Ok(false);
+        }
+        debug!("Pausing {name}");
+        player.pause().await?;
+        Ok(true)
+    }
+
+    /// Resumes exactly the players which were paused by [`MprisInterface::pause_playing`]. A
+    /// player which fails, e.g. because it was closed in the meantime, is skipped and logged.
+    pub async fn resume_paused(&mut self) {
+        for name in std::mem::take(&mut self.paused) {
+            debug!("Resuming {name}");
+            let result = async { self.player(&name).await?.play().await }.await;
+            if let Err(e) = result {
+                warn!("Failed to resume {name}: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use zbus::message::Header;
+
+    /// A stand-in message bus, which lists the names of the players.
+    struct Bus;
+
+    #[zbus::interface(name = "org.freedesktop.DBus")]
+    impl Bus {
+        fn list_names(&self) -> Vec<String> {
+            vec![
+                "org.freedesktop.Notifications".to_string(),
+                format!("{MPRIS_PREFIX}mpv"),
+                format!("{MPRIS_PREFIX}spotify"),
+                format!("{MPRIS_PREFIX}vlc"),
+            ]
+        }
+    }
+
+    /// Stand-in players, told apart by the destination of the calls. All of them are playing, and
+    /// `mpv` fails to pause and play.
+    struct Players {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Players {
+        fn call(&self, method: &str, header: &Header<'_>) -> zbus::fdo::Result<()> {
+            let name = header.destination().unwrap().to_string();
+            let player = name.trim_start_matches(MPRIS_PREFIX);
+            if player == "mpv" {
+                return Err(zbus::fdo::Error::Failed("Player crashed".to_string()));
+            }
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{method} {player}"));
+            Ok(())
+        }
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl Players {
+        fn play(&self, #[zbus(header)] header: Header<'_>) -> zbus::fdo::Result<()> {
+            self.call("play", &header)
+        }
+
+        fn pause(&self, #[zbus(header)] header: Header<'_>) -> zbus::fdo::Result<()> {
+            self.call("pause", &header)
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            "Playing".to_string()
+        }
+    }
+
+    async fn mpris(calls: &Arc<Mutex<Vec<String>>>) -> (Connection, MprisInterface) {
+        let players = Players {
+            calls: Arc::clone(calls),
+        };
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let (server, client) = tokio::try_join!(
+            zbus::connection::Builder::unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/DBus", Bus)
+                .unwrap()
+                .serve_at("/org/mpris/MediaPlayer2", players)
+                .unwrap()
+                .build(),
+            zbus::connection::Builder::unix_stream(client).p2p().build(),
+        )
+        .unwrap();
+        (server, MprisInterface::new(&client))
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (_server, mut mpris) = mpris(&calls).await;
+
+        // The failing player does not keep the others playing
+        mpris.pause_playing().await.unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["pause spotify", "pause vlc"]);
+        assert_eq!(
+            mpris.paused,
+            vec![
+                format!("{MPRIS_PREFIX}spotify"),
+                format!("{MPRIS_PREFIX}vlc")
+            ]
+        );
+
+        // A player which fails to resume is skipped
+        calls.lock().unwrap().clear();
+        mpris.paused.insert(0, format!("{MPRIS_PREFIX}mpv"));
+        mpris.resume_paused().await;
+        assert_eq!(*calls.lock().unwrap(), vec!["play spotify", "play vlc"]);
+        assert!(mpris.paused.is_empty());
+    }
+}
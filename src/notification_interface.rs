@@ -19,8 +19,20 @@ impl NotificationInterface {
     ///
     /// A `Result` containing the new `NotificationInterface` instance.
     pub async fn new() -> Result<Self> {
-        let connection = Connection::session().await?;
-        let proxy = NotificationsProxy::new(&connection).await?;
+        Self::from_connection(&Connection::session().await?).await
+    }
+
+    /// Creates a new instance of `NotificationInterface` on an existing connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection to the session bus.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `NotificationInterface` instance.
+    pub async fn from_connection(connection: &Connection) -> Result<Self> {
+        let proxy = NotificationsProxy::new(connection).await?;
         Ok(Self { proxy })
     }
 
@@ -0,0 +1,146 @@
+use crate::config::OutputsConfig;
+use crate::sway_ipc_interface::SwayIpcInterface;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// The state of an output before the focus session changed it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OutputState {
+    /// Name of the output, e.g. `HDMI-A-1`.
+    pub name: String,
+    /// Whether the output was enabled.
+    pub enabled: bool,
+    /// Whether the output was powered on.
+    pub powered: bool,
+}
+
+/// Outputs which are powered off or disabled during the focus session.
+#[derive(Clone, Debug)]
+pub struct Outputs {
+    config: OutputsConfig,
+}
+
+impl Outputs {
+    /// Creates new `Outputs` from their configuration.
+    pub fn new(config: OutputsConfig) -> Self {
+        Self { config }
+    }
+
+    /// Captures the current state of the configured outputs.
+    ///
+    /// Outputs which are not connected are skipped with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the state of the configured outputs.
+    pub async fn capture(&self, sway: &mut SwayIpcInterface) -> Result<Vec<OutputState>> {
+        let outputs = sway.get_outputs().await?;
+        let mut states = Vec::new();
+        for name in self.config.power_off.iter().chain(&self.config.disable) {
+            match outputs.iter().find(|(n, _, _)| n == name) {
+                Some((name, enabled, powered)) => states.push(OutputState {
+                    name: name.clone(),
+                    enabled: *enabled,
+                    powered: *powered,
+                }),
+                None => warn!("Output '{name}' not found"),
+            }
+        }
+        debug!("Captured output states: {states:?}");
+        Ok(states)
+    }
+
+    /// Powers off or disables the configured outputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    /// * `states` - The captured states, only these outputs are changed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn apply(&self, sway: &mut SwayIpcInterface, states: &[OutputState]) -> Result<()> {
+        for state in states {
+            if self.config.disable.contains(&state.name) {
+                sway.set_output_enabled(&state.name, false).await?;
+            } else {
+                sway.set_output_power(&state.name, false).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Restores outputs to their captured state. Every output is restored, even if restoring another
+/// one failed, e.g. because it was unplugged during the session.
+///
+/// # Arguments
+///
+/// * `sway` - The sway IPC interface.
+/// * `states` - The states captured before the session.
+///
+/// # Returns
+///
+/// A `Result` which is an error naming every output which could not be restored.
+pub async fn restore(sway: &mut SwayIpcInterface, states: &[OutputState]) -> Result<()> {
+    let mut failures = Vec::new();
+    for state in states {
+        let restored = async {
+            if state.enabled {
+                sway.set_output_enabled(&state.name, true).await?;
+            }
+            if state.powered {
+                sway.set_output_power(&state.name, true).await?;
+            }
+            Ok::<_, swayipc_async::Error>(())
+        }
+        .await;
+        if let Err(e) = restored {
+            failures.push(format!("{}: {e}", state.name));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Failed to restore outputs: {}",
+            failures.join("; ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sway_ipc_interface::fake::FakeSway;
+
+    #[tokio::test]
+    async fn test_restore_every_output() {
+        let (mut sway, commands) = FakeSway::default().fail("HDMI-A-1").start();
+        let state = |name: &str| OutputState {
+            name: name.to_string(),
+            enabled: true,
+            powered: true,
+        };
+        let error = restore(&mut sway, &[state("HDMI-A-1"), state("DP-1")])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("HDMI-A-1"));
+        assert!(!error.to_string().contains("DP-1"));
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "output \"HDMI-A-1\" enable",
+                "output \"DP-1\" enable",
+                "output \"DP-1\" power on",
+            ]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use log::trace;
+use regex::Regex;
+
+/// Finds the PIDs of all processes whose command name matches `name`.
+///
+/// The command name is read from `/proc/<pid>/comm`, which the kernel truncates to 15 characters.
+/// The comparison therefore also only uses the first 15 characters of `name`.
+///
+/// # Arguments
+///
+/// * `name` - The process name to search for, e.g. `waybar`.
+///
+/// # Returns
+///
+/// A `Result` containing the list of matching PIDs, sorted in ascending order.
+pub fn find_by_name(name: &str) -> io::Result<Vec<i32>> {
+    let name: String = name.chars().take(15).collect();
+    let pids =
+        find(|dir| fs::read_to_string(dir.join("comm")).is_ok_and(|comm| comm.trim_end() == name))?;
+    trace!("Processes named {name:?}: {pids:?}");
+    Ok(pids)
+}
+
+/// Finds the PIDs of all processes whose command line matches `regex`.
+///
+/// The arguments are read from `/proc/<pid>/cmdline` and joined with spaces. Kernel threads have
+/// no command line and never match.
+///
+/// # Arguments
+///
+/// * `regex` - The pattern to search for in the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the list of matching PIDs, sorted in ascending order.
+pub fn find_by_cmdline(regex: &Regex) -> io::Result<Vec<i32>> {
+    let pids = find(|dir| {
+        let Ok(cmdline) = fs::read(dir.join("cmdline")) else {
+            return false;
+        };
+        let cmdline = String::from_utf8_lossy(&cmdline);
+        let cmdline = cmdline.trim_end_matches('\0').replace('\0', " ");
+        !cmdline.is_empty() && regex.is_match(&cmdline)
+    })?;
+    trace!("Processes with command line matching {regex}: {pids:?}");
+    Ok(pids)
+}
+
+/// Finds the PIDs of all processes for which `matches` returns `true`.
+///
+/// The predicate gets the `/proc/<pid>` directory of the process. Processes which exit in the
+/// meantime are expected to be ignored by the predicate.
+fn find(matches: impl Fn(&Path) -> bool) -> io::Result<Vec<i32>> {
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        if matches(&entry.path()) {
+            pids.push(pid);
+        }
+    }
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+/// Sends a signal to a process.
+///
+/// # Arguments
+///
+/// * `pid` - The PID of the process.
+/// * `signal` - The signal number, see [`parse_signal`].
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub fn send_signal(pid: i32, signal: i32) -> io::Result<()> {
+    trace!("Sending signal {signal} to process {pid}");
+    if unsafe { libc::kill(pid, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Parses a signal name such as `SIGUSR1`, `USR1`, `SIGRTMIN+3` or a plain signal number.
+///
+/// # Arguments
+///
+/// * `input` - The signal name or number.
+///
+/// # Returns
+///
+/// `Some(signal)` with the signal number if the input is valid, otherwise `None`.
+pub fn parse_signal(input: &str) -> Option<i32> {
+    let input = input.trim();
+    if let Ok(number) = input.parse::<i32>() {
+        return (number > 0).then_some(number);
+    }
+    let upper = input.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    if let Some(offset) = name.strip_prefix("RTMIN+") {
+        let signal = libc::SIGRTMIN() + offset.parse::<i32>().ok()?;
+        return (signal <= libc::SIGRTMAX()).then_some(signal);
+    }
+    match name {
+        "HUP" => Some(libc::SIGHUP),
+        "INT" => Some(libc::SIGINT),
+        "QUIT" => Some(libc::SIGQUIT),
+        "KILL" => Some(libc::SIGKILL),
+        "USR1" => Some(libc::SIGUSR1),
+        "USR2" => Some(libc::SIGUSR2),
+        "TERM" => Some(libc::SIGTERM),
+        "CONT" => Some(libc::SIGCONT),
+        "STOP" => Some(libc::SIGSTOP),
+        "RTMIN" => Some(libc::SIGRTMIN()),
+        "RTMAX" => Some(libc::SIGRTMAX()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGUSR1"), Some(libc::SIGUSR1));
+        assert_eq!(parse_signal("usr2"), Some(libc::SIGUSR2));
+        assert_eq!(parse_signal("10"), Some(10));
+        assert_eq!(parse_signal("SIGRTMIN+1"), Some(libc::SIGRTMIN() + 1));
+        assert_eq!(parse_signal("SIGFOO"), None);
+        assert_eq!(parse_signal("0"), None);
+        assert_eq!(parse_signal(""), None);
+    }
+
+    #[test]
+    fn test_find_by_name_self() {
+        let comm = fs::read_to_string("/proc/self/comm").unwrap();
+        let pids = find_by_name(comm.trim_end()).unwrap();
+        assert!(pids.contains(&(std::process::id() as i32)));
+    }
+
+    #[test]
+    fn test_find_by_cmdline_self() {
+        let program = std::env::args().next().unwrap();
+        let regex = Regex::new(&format!("^{}", regex::escape(&program))).unwrap();
+        let pids = find_by_cmdline(&regex).unwrap();
+        assert!(pids.contains(&(std::process::id() as i32)));
+    }
+}
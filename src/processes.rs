@@ -0,0 +1,119 @@
+use crate::config::{ProcessRuleConfig, ProcessesConfig};
+use crate::process;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use regex::Regex;
+
+/// A rule selecting processes to stop.
+#[derive(Debug)]
+enum ProcessRule {
+    /// Matches the process name.
+    Name(String),
+    /// Matches the command line.
+    Cmdline(Regex),
+}
+
+/// Processes which are stopped during the focus session.
+#[derive(Debug, Default)]
+pub struct Processes {
+    rules: Vec<ProcessRule>,
+}
+
+impl Processes {
+    /// Creates new `Processes` from their configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The processes configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Processes`, or an error if a regular expression is invalid.
+    pub fn from_config(config: &ProcessesConfig) -> Result<Self> {
+        let rules = config
+            .stop
+            .iter()
+            .map(|rule| match rule {
+                ProcessRuleConfig::Name(name) => Ok(ProcessRule::Name(name.clone())),
+                ProcessRuleConfig::Cmdline(rule) => Regex::new(&rule.cmdline)
+                    .map(ProcessRule::Cmdline)
+                    .with_context(|| format!("Invalid cmdline pattern '{}'", rule.cmdline)),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Finds the PIDs of all processes matching a rule, except this process.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the PIDs, sorted in ascending order.
+    pub fn find(&self) -> Result<Vec<i32>> {
+        let own_pid = std::process::id() as i32;
+        let mut pids = Vec::new();
+        for rule in &self.rules {
+            pids.extend(match rule {
+                ProcessRule::Name(name) => process::find_by_name(name)?,
+                ProcessRule::Cmdline(regex) => process::find_by_cmdline(regex)?,
+            });
+        }
+        pids.sort_unstable();
+        pids.dedup();
+        pids.retain(|pid| *pid != own_pid);
+        debug!("Processes to stop: {pids:?}");
+        Ok(pids)
+    }
+}
+
+/// Stops the given processes with `SIGSTOP`.
+///
+/// Processes which exited since they were found are skipped.
+///
+/// # Arguments
+///
+/// * `pids` - The PIDs of the processes.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub fn stop(pids: &[i32]) -> Result<()> {
+    signal_all(pids, libc::SIGSTOP)
+}
+
+/// Continues the given processes with `SIGCONT`.
+///
+/// All processes are continued, even if sending the signal to one of them fails. Processes which
+/// exited during the session are skipped.
+///
+/// # Arguments
+///
+/// * `pids` - The PIDs of the stopped processes.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation, the first error is returned.
+pub fn resume(pids: &[i32]) -> Result<()> {
+    signal_all(pids, libc::SIGCONT)
+}
+
+/// Sends a signal to all processes, ignoring the ones which do not exist anymore.
+fn signal_all(pids: &[i32], signal: i32) -> Result<()> {
+    let mut result = Ok(());
+    for &pid in pids {
+        match process::send_signal(pid, signal) {
+            Ok(()) => {}
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {
+                debug!("Process {pid} exited, skipping it");
+            }
+            Err(e) => {
+                warn!("Sending signal {signal} to process {pid} failed: {e}");
+                if result.is_ok() {
+                    result = Err(anyhow::Error::new(e)
+                        .context(format!("Sending signal {signal} to process {pid} failed")));
+                }
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,145 @@
+/// This module keeps track of changes to the desktop which would persist if the focus timer
+/// crashed. The changes are written to a state file when a session starts, and the file is removed
+/// after the session cleaned up. If the file still exists when the next session starts, or when
+/// `focus-time recover` is run, the changes are undone first.
+///
+/// The default file location is `XDG_STATE_HOME/focus-time/recovery.toml`.
+use crate::outputs::{self, OutputState};
+use crate::processes;
+use crate::sway_ipc_interface::SwayIpcInterface;
+
+use anyhow::Result;
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Everything that has to be restored if a session ends without cleaning up.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RecoveryState {
+    /// The original state of outputs that were turned off.
+    #[serde(default)]
+    pub outputs: Vec<OutputState>,
+    /// The PIDs of processes that were stopped.
+    #[serde(default)]
+    pub stopped_processes: Vec<i32>,
+}
+
+impl RecoveryState {
+    /// Checks whether there is nothing to recover.
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty() && self.stopped_processes.is_empty()
+    }
+}
+
+/// Returns the path of the recovery state file.
+fn state_path() -> Result<PathBuf> {
+    Ok(dirs::state_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get state directory"))?
+        .join("focus-time")
+        .join("recovery.toml"))
+}
+
+/// Writes the recovery state, or removes the file if there is nothing to recover.
+///
+/// # Arguments
+///
+/// * `state` - The changes that are about to be made to the desktop.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub fn save(state: &RecoveryState) -> Result<()> {
+    if state.is_empty() {
+        return clear();
+    }
+    let path = state_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content = toml::to_string(state)?;
+    trace!("Writing recovery state to {path:?}: {content:?}");
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Removes the recovery state file after a session cleaned up.
+pub fn clear() -> Result<()> {
+    let path = state_path()?;
+    if path.exists() {
+        debug!("Removing recovery state {path:?}");
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Loads the recovery state left behind by a session which did not clean up.
+fn load() -> Result<Option<RecoveryState>> {
+    let path = state_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)?;
+    Ok(Some(toml::from_str(&content)?))
+}
+
+/// Undoes the changes of a session that did not clean up, if there is one.
+///
+/// Every step is tried, the stopped processes first as they do not depend on sway. Only the
+/// changes which could not be undone are kept in the recovery file for the next attempt.
+///
+/// # Returns
+///
+/// A `Result` containing `true` if something was recovered, or the first error.
+pub async fn recover() -> Result<bool> {
+    let Some(state) = load()? else {
+        debug!("Nothing to recover");
+        return Ok(false);
+    };
+    warn!("Previous session did not clean up, recovering: {state:?}");
+
+    let mut remaining = RecoveryState::default();
+    let mut result = Ok(());
+    if let Err(e) = processes::resume(&state.stopped_processes) {
+        warn!("Failed to continue stopped processes: {e:?}");
+        remaining.stopped_processes = state.stopped_processes;
+        result = Err(e);
+    }
+    if !state.outputs.is_empty() {
+        let restored = async {
+            let mut sway = SwayIpcInterface::new().await?;
+            outputs::restore(&mut sway, &state.outputs).await
+        }
+        .await;
+        if let Err(e) = restored {
+            warn!("Failed to restore outputs: {e:?}");
+            remaining.outputs = state.outputs;
+            result = result.and(Err(e));
+        }
+    }
+
+    save(&remaining)?;
+    result?;
+    info!("Recovered from previous session");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let state = RecoveryState {
+            outputs: vec![OutputState {
+                name: "HDMI-A-1".to_string(),
+                enabled: true,
+                powered: true,
+            }],
+            stopped_processes: vec![1234, 5678],
+        };
+        let content = toml::to_string(&state).unwrap();
+        assert_eq!(toml::from_str::<RecoveryState>(&content).unwrap(), state);
+        assert!(RecoveryState::default().is_empty());
+    }
+}
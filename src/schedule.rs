@@ -0,0 +1,455 @@
+/// This module computes when scheduled sessions start. A session is either scheduled once with
+/// `focus-time schedule`, or on certain days of the week in the configuration file.
+///
+/// Times are wall-clock times in the local or a given time zone. When the clocks are set forward,
+/// a time in the skipped hour is moved by the length of the gap, e.g. 02:30 becomes 03:30. When the
+/// clocks are set back, the first of the two occurrences is used.
+///
+/// Sessions for the events of a calendar file are started through the schedule as well.
+use crate::calendar::Calendar;
+use crate::cli::SessionArgs;
+use crate::config::ScheduleConfig;
+use crate::focus;
+
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Days, Local, LocalResult, NaiveDate, NaiveTime, Offset, TimeDelta,
+    TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use log::{info, warn};
+
+/// Sessions which were missed by more than this, e.g. while the computer was suspended, are
+/// skipped.
+const MISSED_AFTER: TimeDelta = TimeDelta::minutes(15);
+
+/// The time zone of a scheduled time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Zone {
+    /// The local time zone of the system.
+    Local,
+    /// A time zone from the tz database.
+    Named(Tz),
+}
+
+/// When a session is started, and with which options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Days of the week on which the session is started, `None` to start it only once.
+    days: Option<Vec<Weekday>>,
+    /// Time of the day.
+    time: NaiveTime,
+    /// Time zone of the time.
+    zone: Zone,
+    /// The options of the session.
+    pub args: SessionArgs,
+}
+
+/// Parses a time of the day, e.g. `09:00`.
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| anyhow!("Invalid time '{time}', expected e.g. '09:00'"))
+}
+
+/// Converts a wall-clock time in a time zone to UTC.
+///
+/// # Arguments
+///
+/// * `zone` - The time zone.
+/// * `date` - The date.
+/// * `time` - The time of the day.
+///
+/// # Returns
+///
+/// The time in UTC, `None` if it cannot be represented.
+pub fn resolve<Z: TimeZone>(zone: &Z, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    let local = date.and_time(time);
+    match zone.from_local_datetime(&local) {
+        LocalResult::Single(start) => Some(start.with_timezone(&Utc)),
+        // The clocks were set back, the time occurs twice
+        LocalResult::Ambiguous(first, _) => Some(first.with_timezone(&Utc)),
+        // The clocks were set forward, so the time is read with the offset from before the gap
+        LocalResult::None => {
+            let before = zone
+                .from_local_datetime(&(local - TimeDelta::hours(3)))
+                .earliest()?;
+            let offset = TimeDelta::seconds(before.offset().fix().local_minus_utc().into());
+            Some(Utc.from_utc_datetime(&(local - offset)))
+        }
+    }
+}
+
+impl Entry {
+    /// Creates an `Entry` from the configuration of a recurring session.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `Entry`, or an error for an invalid day, time, time zone or
+    /// duration.
+    pub fn from_config(config: &ScheduleConfig) -> Result<Self> {
+        let days = match &config.days {
+            Some(days) => days
+                .iter()
+                .map(|day| {
+                    day.parse::<Weekday>()
+                        .map_err(|_| anyhow!("Invalid day '{day}' in schedule"))
+                })
+                .collect::<Result<_>>()?,
+            None => (0..7)
+                .filter_map(|day| Weekday::try_from(day).ok())
+                .collect(),
+        };
+        let zone = match &config.time_zone {
+            Some(name) => Zone::Named(
+                name.parse()
+                    .map_err(|_| anyhow!("Unknown time zone '{name}' in schedule"))?,
+            ),
+            None => Zone::Local,
+        };
+        if let Some(duration) = &config.duration {
+            focus::parse_duration(duration)
+                .ok_or_else(|| anyhow!("Invalid duration '{duration}' in schedule"))?;
+        }
+        Ok(Self {
+            days: Some(days),
+            time: parse_time(&config.time)?,
+            zone,
+            args: SessionArgs {
+                duration: config.duration.clone(),
+                task: config.task.clone(),
+                workspace: config.workspace.clone(),
+                profile: config.profile.clone(),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Creates an `Entry` for a session which is started once, at the next occurrence of the
+    /// time in the local time zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of the day, e.g. `09:00`.
+    /// * `args` - The options of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `Entry`, or an error for an invalid time.
+    pub fn once(time: &str, args: SessionArgs) -> Result<Self> {
+        Ok(Self {
+            days: None,
+            time: parse_time(time)?,
+            zone: Zone::Local,
+            args,
+        })
+    }
+
+    /// Returns whether the session is started on several days.
+    pub fn is_recurring(&self) -> bool {
+        self.days.is_some()
+    }
+
+    /// Calculates the next start of the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - The start has to be after this time.
+    ///
+    /// # Returns
+    ///
+    /// The next start, `None` if no day of the week is selected.
+    pub fn next_start(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = match self.zone {
+            Zone::Local => after.with_timezone(&Local).date_naive(),
+            Zone::Named(tz) => after.with_timezone(&tz).date_naive(),
+        };
+        (0..=7)
+            .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+            .filter(|date| match &self.days {
+                Some(days) => days.contains(&date.weekday()),
+                None => true,
+            })
+            .filter_map(|date| match self.zone {
+                Zone::Local => resolve(&Local, date, self.time),
+                Zone::Named(tz) => resolve(&tz, date, self.time),
+            })
+            .find(|start| *start > after)
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.days {
+            None => f.write_str("once")?,
+            Some(days) if days.len() == 7 => f.write_str("daily")?,
+            Some(days) => {
+                let days: Vec<_> = days.iter().map(Weekday::to_string).collect();
+                f.write_str(&days.join(","))?;
+            }
+        }
+        write!(f, " at {}", self.time.format("%H:%M"))?;
+        if let Zone::Named(tz) = self.zone {
+            write!(f, " {tz}")?;
+        }
+        if let Some(duration) = &self.args.duration {
+            write!(f, ", {duration}")?;
+        }
+        if let Some(task) = &self.args.task {
+            write!(f, " - {task}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A scheduled session with its next start.
+#[derive(Clone, Debug)]
+pub struct Scheduled {
+    /// The id, used to cancel the session.
+    pub id: u32,
+    /// When the session is started.
+    pub entry: Entry,
+    /// Whether the session comes from the configuration file, such sessions cannot be cancelled.
+    pub from_config: bool,
+    /// The next start, `None` once a session scheduled once has started.
+    pub next: Option<DateTime<Utc>>,
+}
+
+/// All scheduled sessions.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    entries: Vec<Scheduled>,
+    /// The id of the last added session.
+    last_id: u32,
+    /// The calendar with events which start sessions.
+    calendar: Option<Calendar>,
+    /// Events of the calendar which start until this time have been handled.
+    calendar_checked: DateTime<Utc>,
+}
+
+impl Schedule {
+    /// Creates a `Schedule` with the recurring sessions of the configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `configs` - The configuration of the sessions.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `Schedule`, or an error for an invalid session.
+    pub fn from_config(configs: &[ScheduleConfig], now: DateTime<Utc>) -> Result<Self> {
+        let mut schedule = Self::default();
+        for config in configs {
+            schedule.add(Entry::from_config(config)?, true, now);
+        }
+        Ok(schedule)
+    }
+
+    /// Adds a calendar with events which start sessions. Events which started shortly before
+    /// `now` and have not ended yet start a session right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `calendar` - The calendar.
+    /// * `now` - The current time.
+    pub fn with_calendar(self, calendar: Calendar, now: DateTime<Utc>) -> Self {
+        Self {
+            calendar: Some(calendar),
+            calendar_checked: now - MISSED_AFTER,
+            ..self
+        }
+    }
+
+    /// Adds a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - When the session is started.
+    /// * `from_config` - Whether the session comes from the configuration file.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// The added session.
+    pub fn add(&mut self, entry: Entry, from_config: bool, now: DateTime<Utc>) -> &Scheduled {
+        self.last_id += 1;
+        self.entries.push(Scheduled {
+            id: self.last_id,
+            next: entry.next_start(now),
+            entry,
+            from_config,
+        });
+        self.entries.last().unwrap()
+    }
+
+    /// Cancels a session which was scheduled once.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the session.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub fn cancel(&mut self, id: u32) -> Result<()> {
+        let index = self
+            .entries
+            .iter()
+            .position(|scheduled| scheduled.id == id)
+            .ok_or_else(|| anyhow!("No session is scheduled with id {id}"))?;
+        if self.entries[index].from_config {
+            return Err(anyhow!(
+                "Session {id} is scheduled in the configuration file and cannot be cancelled"
+            ));
+        }
+        self.entries.remove(index);
+        Ok(())
+    }
+
+    /// Returns the scheduled sessions, ordered by their next start.
+    pub fn entries(&self) -> Vec<Scheduled> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|scheduled| scheduled.next);
+        entries
+    }
+
+    /// Returns the next start of any session.
+    pub fn next_start(&self) -> Option<DateTime<Utc>> {
+        let calendar = self
+            .calendar
+            .as_ref()
+            .and_then(|calendar| calendar.next_start(self.calendar_checked));
+        self.entries
+            .iter()
+            .filter_map(|scheduled| scheduled.next)
+            .chain(calendar)
+            .min()
+    }
+
+    /// Takes the sessions which are due. Sessions which were missed by too much are skipped, and
+    /// sessions scheduled once are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// The options of the sessions to start.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<SessionArgs> {
+        let mut due = Vec::new();
+        for scheduled in &mut self.entries {
+            let Some(next) = scheduled.next.filter(|next| *next <= now) else {
+                continue;
+            };
+            if now - next > MISSED_AFTER {
+                info!("Skipping missed session: {}", scheduled.entry);
+            } else {
+                info!("Scheduled session is due: {}", scheduled.entry);
+                due.push(scheduled.entry.args.clone());
+            }
+            scheduled.next = if scheduled.entry.is_recurring() {
+                scheduled.entry.next_start(now)
+            } else {
+                None
+            };
+        }
+        self.entries.retain(|scheduled| scheduled.next.is_some());
+
+        if let Some(calendar) = &mut self.calendar {
+            if let Err(e) = calendar.refresh() {
+                warn!("Failed to read the calendar: {e:#}");
+            }
+            for event in calendar.due(self.calendar_checked, now) {
+                info!("Calendar event is due: {}", event.summary);
+                due.push(event.args());
+            }
+            self.calendar_checked = now;
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(days: &[&str], time: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            days: Some(days.iter().map(|day| day.to_string()).collect()),
+            time: time.to_string(),
+            time_zone: Some("Europe/Berlin".to_string()),
+            duration: Some("50m".to_string()),
+            task: Some("Deep work".to_string()),
+            workspace: None,
+            profile: None,
+        }
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn test_next_start() {
+        let entry = Entry::from_config(&config(&["mon", "fri"], "09:00")).unwrap();
+        assert_eq!(
+            entry.to_string(),
+            "Mon,Fri at 09:00 Europe/Berlin, 50m - Deep work"
+        );
+        // Friday before the clocks are set forward, then the Monday after
+        let friday = utc("2026-03-27T08:00:00Z");
+        assert_eq!(entry.next_start(utc("2026-03-26T12:00:00Z")), Some(friday));
+        assert_eq!(entry.next_start(friday), Some(utc("2026-03-30T07:00:00Z")));
+
+        // The clocks are set forward on 2026-03-29 at 02:00 and back on 2026-10-25 at 03:00
+        let gap = Entry::from_config(&config(&["sun"], "02:30")).unwrap();
+        assert_eq!(
+            gap.next_start(utc("2026-03-28T12:00:00Z")),
+            Some(utc("2026-03-29T01:30:00Z"))
+        );
+        let twice = Entry::from_config(&config(&["sun"], "02:30")).unwrap();
+        assert_eq!(
+            twice.next_start(utc("2026-10-24T12:00:00Z")),
+            Some(utc("2026-10-25T00:30:00Z"))
+        );
+
+        let mut invalid = config(&["someday"], "09:00");
+        assert!(Entry::from_config(&invalid).is_err());
+        invalid = config(&["mon"], "9am");
+        assert!(Entry::from_config(&invalid).is_err());
+        invalid.time = "09:00".to_string();
+        invalid.time_zone = Some("Europe/Atlantis".to_string());
+        assert!(Entry::from_config(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_take_due() {
+        let now = utc("2026-10-19T06:00:00Z");
+        let mut schedule = Schedule::from_config(&[config(&["mon"], "09:00")], now).unwrap();
+        let once = Entry {
+            zone: Zone::Named(chrono_tz::Europe::Berlin),
+            ..Entry::once("10:00", SessionArgs::default()).unwrap()
+        };
+        let id = schedule.add(once, false, now).id;
+        assert!(schedule.cancel(1).is_err());
+        assert_eq!(schedule.next_start(), Some(utc("2026-10-19T07:00:00Z")));
+
+        assert!(schedule.take_due(utc("2026-10-19T06:59:00Z")).is_empty());
+        let due = schedule.take_due(utc("2026-10-19T07:00:10Z"));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].task.as_deref(), Some("Deep work"));
+        let entries = schedule.entries();
+        assert_eq!(entries[0].next, Some(utc("2026-10-19T08:00:00Z")));
+        assert_eq!(entries[1].next, Some(utc("2026-10-26T08:00:00Z")));
+
+        // A session missed while the computer was suspended is skipped
+        assert!(schedule.take_due(utc("2026-10-19T09:00:00Z")).is_empty());
+        assert_eq!(schedule.entries().len(), 1);
+        assert!(schedule.cancel(id).is_err());
+    }
+}
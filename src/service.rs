@@ -0,0 +1,246 @@
+/// This module installs the files that let D-Bus and systemd start the daemon on demand. With
+/// them installed, `focus-time start` or `focus-time status` activate the daemon when it is not
+/// running yet.
+///
+/// The D-Bus activation file is written to `XDG_DATA_HOME/dbus-1/services/` and the systemd user
+/// unit to `XDG_CONFIG_HOME/systemd/user/`. Both contain the path and version of the binary, so
+/// that they can be checked for being out of date.
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The name of the systemd user unit.
+const UNIT_NAME: &str = "focus-time.service";
+
+/// The state of an installed file compared to the file the binary would write.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FileStatus {
+    /// The file is not installed.
+    Missing,
+    /// The file matches the binary.
+    UpToDate,
+    /// The file was written by another version or for another path of the binary.
+    OutOfDate,
+}
+
+/// A file to install, with its location and content.
+#[derive(Debug)]
+struct ServiceFile {
+    path: PathBuf,
+    content: String,
+}
+
+impl ServiceFile {
+    /// Compares the installed file with the content it should have.
+    fn status(&self) -> FileStatus {
+        match std::fs::read_to_string(&self.path) {
+            Ok(installed) if installed == self.content => FileStatus::UpToDate,
+            Ok(_) => FileStatus::OutOfDate,
+            Err(_) => FileStatus::Missing,
+        }
+    }
+}
+
+/// Quotes a path for the `Exec` lines of the D-Bus activation file and the systemd unit, so that
+/// paths with spaces or quotes are passed as one argument.
+///
+/// # Arguments
+///
+/// * `exe` - The path of the binary.
+/// * `systemd` - Whether to also escape the `%` specifiers and `$` variables of systemd.
+fn quote(exe: &Path, systemd: bool) -> String {
+    let mut quoted = String::from("\"");
+    for c in exe.display().to_string().chars() {
+        match c {
+            '\\' | '"' => quoted.push('\\'),
+            '%' | '$' if systemd => quoted.push(c),
+            _ => {}
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the content of the D-Bus activation file.
+///
+/// # Arguments
+///
+/// * `exe` - The path of the binary.
+fn dbus_service(exe: &Path) -> String {
+    format!(
+        "# Generated by focus-time {}\n\
+         [D-BUS Service]\n\
+         Name=org.towoe.FocusTime\n\
+         Exec={} daemon\n\
+         SystemdService={UNIT_NAME}\n",
+        env!("CARGO_PKG_VERSION"),
+        quote(exe, false)
+    )
+}
+
+/// Returns the content of the systemd user unit.
+///
+/// # Arguments
+///
+/// * `exe` - The path of the binary.
+fn systemd_unit(exe: &Path) -> String {
+    format!(
+        "# Generated by focus-time {}\n\
+         [Unit]\n\
+         Description=Focus Time daemon\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         Type=dbus\n\
+         BusName=org.towoe.FocusTime\n\
+         ExecStart={} daemon\n",
+        env!("CARGO_PKG_VERSION"),
+        quote(exe, true)
+    )
+}
+
+/// Returns the files to install for the running binary.
+fn service_files() -> Result<Vec<ServiceFile>> {
+    let exe = std::env::current_exe()?;
+    let data_dir = dirs::data_dir().ok_or_else(|| anyhow!("Failed to get data directory"))?;
+    let config_dir = dirs::config_dir().ok_or_else(|| anyhow!("Failed to get config directory"))?;
+    Ok(vec![
+        ServiceFile {
+            path: data_dir
+                .join("dbus-1")
+                .join("services")
+                .join("org.towoe.FocusTime.service"),
+            content: dbus_service(&exe),
+        },
+        ServiceFile {
+            path: config_dir.join("systemd").join("user").join(UNIT_NAME),
+            content: systemd_unit(&exe),
+        },
+    ])
+}
+
+/// Tells systemd to reload the user units, a failure is only logged.
+fn reload_systemd() {
+    match Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status()
+    {
+        Ok(status) if status.success() => debug!("Reloaded systemd user units"),
+        Ok(status) => warn!("systemctl --user daemon-reload failed: {status}"),
+        Err(e) => warn!("Failed to run systemctl: {e}"),
+    }
+}
+
+/// Writes the D-Bus activation file and the systemd user unit.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub fn install() -> Result<()> {
+    for file in service_files()? {
+        if let Some(dir) = file.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&file.path, &file.content)?;
+        println!("Installed {}", file.path.display());
+    }
+    reload_systemd();
+    Ok(())
+}
+
+/// Removes the D-Bus activation file and the systemd user unit.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the operation.
+pub fn uninstall() -> Result<()> {
+    for file in service_files()? {
+        if file.path.exists() {
+            std::fs::remove_file(&file.path)?;
+            println!("Removed {}", file.path.display());
+        }
+    }
+    reload_systemd();
+    Ok(())
+}
+
+/// Prints whether the installed files are up to date with the running binary.
+///
+/// # Returns
+///
+/// A `Result` which is an error if a file is missing or out of date.
+pub fn check() -> Result<()> {
+    let mut up_to_date = true;
+    for file in service_files()? {
+        let status = file.status();
+        let text = match status {
+            FileStatus::Missing => "not installed",
+            FileStatus::UpToDate => "up to date",
+            FileStatus::OutOfDate => "out of date",
+        };
+        println!("{}: {text}", file.path.display());
+        up_to_date &= status == FileStatus::UpToDate;
+    }
+    if !up_to_date {
+        return Err(anyhow!(
+            "The service files do not match this binary, run `focus-time install-service`"
+        ));
+    }
+    Ok(())
+}
+
+/// Logs a warning if installed files are out of date, e.g. when the daemon starts.
+pub fn warn_if_out_of_date() {
+    let Ok(files) = service_files() else {
+        return;
+    };
+    for file in files {
+        if file.status() == FileStatus::OutOfDate {
+            warn!(
+                "{} is out of date, run `focus-time install-service`",
+                file.path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_status() {
+        let dir = std::env::temp_dir().join(format!("focus-time-service-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = ServiceFile {
+            path: dir.join(UNIT_NAME),
+            content: systemd_unit(Path::new("/usr/bin/focus-time")),
+        };
+        assert_eq!(file.status(), FileStatus::Missing);
+        std::fs::write(&file.path, &file.content).unwrap();
+        assert_eq!(file.status(), FileStatus::UpToDate);
+        std::fs::write(
+            &file.path,
+            systemd_unit(Path::new("/usr/local/bin/focus-time")),
+        )
+        .unwrap();
+        assert_eq!(file.status(), FileStatus::OutOfDate);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_quote() {
+        let exe = Path::new("/home/me/My \"Apps\"/100%/focus-time");
+        assert_eq!(
+            quote(exe, false),
+            r#""/home/me/My \"Apps\"/100%/focus-time""#
+        );
+        assert_eq!(
+            quote(exe, true),
+            r#""/home/me/My \"Apps\"/100%%/focus-time""#
+        );
+    }
+}
@@ -0,0 +1,321 @@
+/// This module contains the event core of a running focus session. Commands, e.g. from D-Bus or
+/// the distraction guard, are sent over a channel and each gets a reply. Every change of the
+/// session is published as a new `SessionState`, which all consumers subscribe to, so that they
+/// only wake up when something changed.
+use crate::timer::Timer;
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::time::{Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// A command for the running focus session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Stop the session.
+    Stop,
+    /// Pause or resume the timer.
+    TogglePause,
+    /// Pause the timer automatically, from the given time on.
+    Pause(Instant),
+    /// Resume the timer if it was paused automatically.
+    Resume,
+    /// Count a blocked window getting focus.
+    RecordInterruption,
+}
+
+/// Why a focus session finished.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FinishReason {
+    /// The timer ran out.
+    Completed,
+    /// The session was stopped over D-Bus.
+    Stopped,
+    /// The session was aborted with Ctrl+C.
+    Aborted,
+}
+
+/// The phase in the lifecycle of a focus session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// The desktop is being prepared.
+    Starting,
+    /// The timer is running.
+    Running,
+    /// The timer was paused manually.
+    Paused,
+    /// The timer ran out, the session is about to finish.
+    Overtime,
+    /// The timer was paused automatically while the user is away.
+    Break,
+    /// The desktop is being restored.
+    Finishing,
+    /// The session is over.
+    Finished(FinishReason),
+}
+
+impl Phase {
+    /// Returns the name of the phase, as used on D-Bus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Overtime => "overtime",
+            Self::Break => "break",
+            Self::Finishing => "finishing",
+            Self::Finished(_) => "finished",
+        }
+    }
+
+    /// Checks whether the session can change from this phase to another one.
+    pub fn can_change_to(&self, next: Phase) -> bool {
+        use Phase::*;
+        matches!(
+            (self, next),
+            (Starting, Running | Finishing)
+                | (Running, Paused | Break | Overtime | Finishing)
+                | (Paused, Running | Finishing)
+                | (Break, Running | Finishing)
+                | (Overtime, Finishing)
+                | (Finishing, Finished(_))
+        )
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Finished(reason) => {
+                let reason = format!("{reason:?}").to_lowercase();
+                write!(f, "{} ({reason})", self.name())
+            }
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// A change of the session phase.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    /// The time of the change.
+    pub time: SystemTime,
+    /// The phase the session changed to.
+    pub phase: Phase,
+}
+
+/// A snapshot of the focus session.
+#[derive(Clone, Debug)]
+pub struct SessionState {
+    /// The phase of the session.
+    pub phase: Phase,
+    /// The timer, its remaining time stays valid until the next change.
+    pub timer: Timer,
+    /// Number of times a blocked window got focus.
+    pub interruptions: u32,
+    /// Every phase of the session so far, starting with `Starting`.
+    pub transitions: Vec<Transition>,
+}
+
+impl SessionState {
+    /// Creates the state of a new session.
+    pub fn new(timer: Timer) -> Self {
+        Self {
+            phase: Phase::Starting,
+            timer,
+            interruptions: 0,
+            transitions: vec![Transition {
+                time: SystemTime::now(),
+                phase: Phase::Starting,
+            }],
+        }
+    }
+
+    /// Changes the phase of the session and records the transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `next` - The new phase.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the change is allowed in the current phase.
+    pub fn change_to(&mut self, next: Phase) -> Result<()> {
+        if !self.phase.can_change_to(next) {
+            return Err(anyhow!(
+                "The session cannot change from {} to {next}",
+                self.phase
+            ));
+        }
+        debug!("Session phase: {} -> {next}", self.phase);
+        self.phase = next;
+        self.transitions.push(Transition {
+            time: SystemTime::now(),
+            phase: next,
+        });
+        Ok(())
+    }
+
+    /// Applies a command to the state. Stopping is handled by the session itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to apply.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the command is allowed in the current phase.
+    pub fn apply(&mut self, command: Command) -> Result<()> {
+        match (command, self.phase) {
+            (Command::Stop, _) => {}
+            (Command::TogglePause, Phase::Running) => {
+                self.change_to(Phase::Paused)?;
+                self.timer.pause();
+            }
+            (Command::TogglePause, Phase::Paused | Phase::Break) => {
+                self.change_to(Phase::Running)?;
+                self.timer.resume();
+            }
+            (Command::TogglePause, phase) => {
+                return Err(anyhow!("The timer cannot be paused while {phase}"));
+            }
+            (Command::Pause(since), Phase::Running) => {
+                self.change_to(Phase::Break)?;
+                self.timer.pause_since(since);
+            }
+            // Only an automatic pause is resumed automatically
+            (Command::Resume, Phase::Break) => {
+                self.change_to(Phase::Running)?;
+                self.timer.resume();
+            }
+            (Command::Pause(_) | Command::Resume, _) => {}
+            (Command::RecordInterruption, _) => self.interruptions += 1,
+        }
+        Ok(())
+    }
+}
+
+/// A command together with the channel for its reply, the state after the command.
+pub type Request = (Command, oneshot::Sender<Result<SessionState>>);
+
+/// A handle to the running focus session, which can be cloned and passed to every consumer.
+#[derive(Clone, Debug)]
+pub struct SessionHandle {
+    commands: mpsc::Sender<Request>,
+    state: watch::Receiver<SessionState>,
+}
+
+impl SessionHandle {
+    /// Creates the channels of a new session.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The initial state of the session.
+    ///
+    /// # Returns
+    ///
+    /// The handle, the receiver for the commands and the sender for the state.
+    pub fn new(
+        state: SessionState,
+    ) -> (Self, mpsc::Receiver<Request>, watch::Sender<SessionState>) {
+        let (commands, commands_rx) = mpsc::channel(16);
+        let (state_tx, state) = watch::channel(state);
+        (Self { commands, state }, commands_rx, state_tx)
+    }
+
+    /// Sends a command to the session and waits for the reply.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to send.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the state after the command, or an error if the session has ended or
+    /// the command is not allowed in the current phase.
+    pub async fn send(&self, command: Command) -> Result<SessionState> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.commands
+            .send((command, reply))
+            .await
+            .map_err(|_| anyhow::anyhow!("The focus session has ended"))?;
+        reply_rx.await?
+    }
+
+    /// Returns the current state of the session.
+    pub fn state(&self) -> SessionState {
+        self.state.borrow().clone()
+    }
+
+    /// Subscribes to the changes of the session state.
+    pub fn subscribe(&self) -> watch::Receiver<SessionState> {
+        self.state.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_apply() {
+        let mut state = SessionState::new(Timer::new(Duration::from_secs(60)));
+        assert!(state.apply(Command::TogglePause).is_err());
+        state.change_to(Phase::Running).unwrap();
+        state.apply(Command::RecordInterruption).unwrap();
+        assert_eq!(state.interruptions, 1);
+
+        // Only an automatic pause is resumed automatically
+        state.apply(Command::TogglePause).unwrap();
+        state.apply(Command::Resume).unwrap();
+        assert_eq!(state.phase, Phase::Paused);
+        assert!(state.timer.is_paused());
+        state.apply(Command::TogglePause).unwrap();
+        state.apply(Command::Pause(Instant::now())).unwrap();
+        assert_eq!(state.phase, Phase::Break);
+        state.apply(Command::Resume).unwrap();
+        assert_eq!(state.phase, Phase::Running);
+        assert!(!state.timer.is_paused());
+
+        // A manual resume ends an automatic pause
+        state.apply(Command::Pause(Instant::now())).unwrap();
+        state.apply(Command::TogglePause).unwrap();
+        assert_eq!(state.phase, Phase::Running);
+
+        let phases: Vec<_> = state.transitions.iter().map(|t| t.phase).collect();
+        assert_eq!(
+            phases,
+            vec![
+                Phase::Starting,
+                Phase::Running,
+                Phase::Paused,
+                Phase::Running,
+                Phase::Break,
+                Phase::Running,
+                Phase::Break,
+                Phase::Running
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut state = SessionState::new(Timer::new(Duration::from_secs(60)));
+        assert!(state.change_to(Phase::Overtime).is_err());
+        // A session whose setup failed cleans up right away
+        assert!(state.phase.can_change_to(Phase::Finishing));
+        state.change_to(Phase::Running).unwrap();
+        state.change_to(Phase::Overtime).unwrap();
+        assert!(state.change_to(Phase::Running).is_err());
+        assert!(state
+            .change_to(Phase::Finished(FinishReason::Completed))
+            .is_err());
+        state.change_to(Phase::Finishing).unwrap();
+        state
+            .change_to(Phase::Finished(FinishReason::Completed))
+            .unwrap();
+        assert_eq!(state.phase.to_string(), "finished (completed)");
+        assert_eq!(state.transitions.len(), 5);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::config::StashConfig;
+use crate::sway_ipc_interface::{self, Placement, SwayIpcInterface, WindowInfo};
+use crate::window_rule::{self, WindowRule};
+
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
+use log::{debug, warn};
+use std::sync::{Arc, Mutex};
+use swayipc_async::{Event, WindowChange};
+use tokio::task::JoinHandle;
+
+/// The place where stashed windows are kept during the focus session.
+#[derive(Clone, Debug)]
+enum StashTarget {
+    /// The sway scratchpad.
+    Scratchpad,
+    /// A workspace which is not shown during the session.
+    Workspace(String),
+}
+
+/// Moves windows matching a set of rules out of sight for the duration of a focus session.
+#[derive(Clone, Debug)]
+pub struct Stash {
+    rules: Vec<WindowRule>,
+    target: StashTarget,
+}
+
+/// A stashed window with its place in the layout before it was stashed.
+#[derive(Clone, Debug)]
+struct StashedWindow {
+    window: WindowInfo,
+    placement: Option<Placement>,
+}
+
+/// A running stash, which keeps track of the stashed windows and catches new matching windows.
+pub struct StashSession {
+    stash: Stash,
+    stashed: Arc<Mutex<Vec<StashedWindow>>>,
+    watcher: JoinHandle<()>,
+}
+
+impl Stash {
+    /// Creates a new `Stash` from its configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The stash configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Stash`, or an error if a rule is invalid.
+    pub fn from_config(config: &StashConfig) -> Result<Self> {
+        let rules = config
+            .rule
+            .iter()
+            .map(WindowRule::from_config)
+            .collect::<Result<_>>()?;
+        let target = match config.target.as_deref() {
+            None | Some("scratchpad") => StashTarget::Scratchpad,
+            Some(workspace) => StashTarget::Workspace(workspace.to_string()),
+        };
+        Ok(Self { rules, target })
+    }
+
+    /// Stashes all matching windows and starts watching for new windows.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the running `StashSession`, or an error if a window could not be
+    /// stashed. The windows stashed before are moved back then, as there is no session to restore
+    /// them.
+    pub async fn start(&self, sway: &mut SwayIpcInterface) -> Result<StashSession> {
+        let mut stashed = Vec::new();
+        for window in sway.get_windows().await? {
+            if !self.should_stash(&window) {
+                continue;
+            }
+            match self.stash_window(sway, window).await {
+                Ok(window) => stashed.push(window),
+                Err(e) => {
+                    if let Err(restore) = self.unstash_all(sway, stashed).await {
+                        warn!("{restore:#}");
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let stashed = Arc::new(Mutex::new(stashed));
+
+        let stash = self.clone();
+        let watched = Arc::clone(&stashed);
+        let watcher = sway_ipc_interface::spawn_watcher("new windows to stash", move || {
+            let stash = stash.clone();
+            let watched = Arc::clone(&watched);
+            async move { stash.watch_windows(watched).await }
+        });
+
+        Ok(StashSession {
+            stash: self.clone(),
+            stashed,
+            watcher,
+        })
+    }
+
+    fn should_stash(&self, window: &WindowInfo) -> bool {
+        let on_target =
+            matches!(&self.target, StashTarget::Workspace(ws) if *ws == window.workspace);
+        !on_target && window_rule::any_matches(&self.rules, window)
+    }
+
+    /// Moves a window to the stash, after capturing its place in the layout.
+    async fn stash_window(
+        &self,
+        sway: &mut SwayIpcInterface,
+        window: WindowInfo,
+    ) -> Result<StashedWindow> {
+        let placement = sway.get_placement(window.id).await?;
+        debug!("Stashing window {window:?} from {placement:?}");
+        match &self.target {
+            StashTarget::Scratchpad => sway.move_window_to_scratchpad(window.id).await?,
+            StashTarget::Workspace(ws) => sway.move_window_to_workspace(window.id, ws).await?,
+        }
+        Ok(StashedWindow { window, placement })
+    }
+
+    /// Moves a window back to its workspace and its place in the layout.
+    async fn unstash_window(
+        &self,
+        sway: &mut SwayIpcInterface,
+        stashed: &StashedWindow,
+    ) -> Result<()> {
+        let window = &stashed.window;
+        debug!("Restoring window {window:?}");
+        match &self.target {
+            StashTarget::Scratchpad => {
+                sway.restore_window_from_scratchpad(window.id, &window.workspace, window.floating)
+                    .await?
+            }
+            StashTarget::Workspace(_) => {
+                sway.move_window_to_workspace(window.id, &window.workspace)
+                    .await?
+            }
+        }
+        if let Some(placement) = &stashed.placement {
+            sway.restore_placement(window.id, placement).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves stashed windows back, the last stashed first. Every window is tried, even if moving
+    /// another one failed, e.g. because it was closed in the meantime.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an error naming every window which could not be moved back.
+    async fn unstash_all(
+        &self,
+        sway: &mut SwayIpcInterface,
+        stashed: Vec<StashedWindow>,
+    ) -> Result<()> {
+        let mut failures = Vec::new();
+        for window in stashed.iter().rev() {
+            if let Err(e) = self.unstash_window(sway, window).await {
+                failures.push(format!("window {}: {e:#}", window.window.id));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Failed to restore stashed windows: {}",
+                failures.join("; ")
+            ))
+        }
+    }
+
+    /// Stashes windows which are opened during the session, or get a matching title later on.
+    async fn watch_windows(&self, stashed: Arc<Mutex<Vec<StashedWindow>>>) -> Result<()> {
+        let mut events = SwayIpcInterface::subscribe_window_events().await?;
+        let mut sway = SwayIpcInterface::new().await?;
+        while let Some(event) = events.next().await {
+            let Event::Window(event) = event? else {
+                continue;
+            };
+            if !matches!(event.change, WindowChange::New | WindowChange::Title) {
+                continue;
+            }
+            let id = event.container.id;
+            if stashed.lock().unwrap().iter().any(|s| s.window.id == id) {
+                continue;
+            }
+            // Look the window up in the tree to learn its workspace
+            let Some(window) = sway.get_window(id).await? else {
+                continue;
+            };
+            if self.should_stash(&window) {
+                let window = self.stash_window(&mut sway, window).await?;
+                stashed.lock().unwrap().push(window);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StashSession {
+    /// Stops watching for new windows and moves all stashed windows back to their workspaces. A
+    /// window which cannot be moved back does not keep the others in the stash.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn restore(self, sway: &mut SwayIpcInterface) -> Result<()> {
+        self.watcher.abort();
+        let stashed = std::mem::take(&mut *self.stashed.lock().unwrap());
+        self.stash.unstash_all(sway, stashed).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sway_ipc_interface::fake::{self, FakeSway};
+
+    fn stash() -> Stash {
+        let config: StashConfig = toml::from_str("[[rule]]\napp-id = \"foot\"").unwrap();
+        Stash::from_config(&config).unwrap()
+    }
+
+    /// A workspace with the windows 2 and 3.
+    fn tree() -> serde_json::Value {
+        fake::node(
+            1,
+            "workspace",
+            "splith",
+            vec![
+                fake::node(2, "con", "none", vec![], vec![]),
+                fake::node(3, "con", "none", vec![], vec![]),
+            ],
+            vec![],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_start_fails_halfway() {
+        let (mut sway, commands) = FakeSway::default()
+            .reply(fake::GET_TREE, tree())
+            .fail("[con_id=3] move scratchpad")
+            .start();
+        assert!(stash().start(&mut sway).await.is_err());
+
+        // The window stashed before the failure is moved back
+        let commands = commands.lock().unwrap();
+        assert_eq!(
+            commands[..2],
+            ["[con_id=2] move scratchpad", "[con_id=3] move scratchpad"]
+        );
+        assert!(commands[2].starts_with("[con_id=2] scratchpad show"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_every_window() {
+        let (mut sway, commands) = FakeSway::default()
+            .reply(fake::GET_TREE, tree())
+            .fail("[con_id=3] scratchpad show")
+            .start();
+        let stash = stash();
+        let windows = sway.get_windows().await.unwrap();
+        let session = StashSession {
+            stash: stash.clone(),
+            stashed: Arc::new(Mutex::new(
+                windows
+                    .into_iter()
+                    .map(|window| StashedWindow {
+                        window,
+                        placement: None,
+                    })
+                    .collect(),
+            )),
+            watcher: tokio::spawn(async {}),
+        };
+
+        // Window 3 fails, window 2 is still moved back
+        let error = session.restore(&mut sway).await.unwrap_err();
+        assert!(error.to_string().contains("window 3"));
+        let commands = commands.lock().unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[1].starts_with("[con_id=2] scratchpad show"));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::config::SwayCommandConfig;
+
+/// Sway commands to run at the start and at the end of the focus session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwayCommands {
+    /// Commands to run at the start of the session.
+    pub on_start: Vec<String>,
+    /// Commands to run at the end of the session.
+    pub on_end: Vec<String>,
+}
+
+impl SwayCommands {
+    /// Creates new `SwayCommands` from the configuration.
+    ///
+    /// If no end commands are configured, the `undo` commands of the start commands are used in
+    /// reverse order, so that the last change is undone first.
+    ///
+    /// # Arguments
+    ///
+    /// * `on_start` - The commands to run at the start, optionally with their inverse.
+    /// * `on_end` - The commands to run at the end.
+    pub fn from_config(on_start: Vec<SwayCommandConfig>, on_end: Option<Vec<String>>) -> Self {
+        let on_end = on_end.unwrap_or_else(|| {
+            on_start
+                .iter()
+                .rev()
+                .filter_map(|command| match command {
+                    SwayCommandConfig::Command(_) => None,
+                    SwayCommandConfig::WithUndo(pair) => Some(pair.undo.clone()),
+                })
+                .collect()
+        });
+        let on_start = on_start
+            .into_iter()
+            .map(|command| match command {
+                SwayCommandConfig::Command(command) => command,
+                SwayCommandConfig::WithUndo(pair) => pair.command,
+            })
+            .collect();
+        Self { on_start, on_end }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SwayConfig;
+
+    const CONFIG: &str = r#"
+on-start = [
+    "gaps inner all set 20",
+    { command = "output * bg focus.png fill", undo = "output * bg normal.png fill" },
+    { command = "default_border pixel 4", undo = "default_border pixel 2" },
+]
+"#;
+
+    #[test]
+    fn test_default_on_end() {
+        let config: SwayConfig = toml::from_str(CONFIG).unwrap();
+        let commands = SwayCommands::from_config(config.on_start.unwrap(), config.on_end);
+        assert_eq!(
+            commands.on_start,
+            vec![
+                "gaps inner all set 20",
+                "output * bg focus.png fill",
+                "default_border pixel 4"
+            ]
+        );
+        assert_eq!(
+            commands.on_end,
+            vec!["default_border pixel 2", "output * bg normal.png fill"]
+        );
+    }
+
+    #[test]
+    fn test_explicit_on_end() {
+        let config: SwayConfig =
+            toml::from_str(&format!("{CONFIG}\non-end = [\"gaps inner all set 0\"]")).unwrap();
+        let commands = SwayCommands::from_config(config.on_start.unwrap(), config.on_end);
+        assert_eq!(commands.on_end, vec!["gaps inner all set 0"]);
+    }
+}
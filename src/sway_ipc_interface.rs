@@ -1,5 +1,130 @@
-use log::{debug, trace};
-use swayipc_async::{BarMode, Connection};
+use async_io::Async;
+use log::{debug, trace, warn};
+use std::future::Future;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+use swayipc_async::{BarMode, Connection, EventStream, EventType, Node, NodeLayout, NodeType};
+use tokio::task::JoinHandle;
+
+/// How often to try connecting to sway again after the connection was lost.
+const RECONNECT_ATTEMPTS: u32 = 10;
+/// Delay between two attempts to connect to sway.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// Mark used for a moment to move a window next to another one.
+const RESTORE_MARK: &str = "_focus_time_restore";
+
+/// Calls a query method of the sway connection. If the connection was lost, e.g. because sway was
+/// restarted, it reconnects and calls the method once more. Only used for queries, which can be
+/// sent twice without harm, see [`SwayIpcInterface::run_command`] for commands.
+macro_rules! retry {
+    ($self:ident, $method:ident($($arg:expr),*)) => {
+        match $self.connection.$method($($arg),*).await {
+            Err(e) if is_disconnect(&e) => {
+                warn!("Lost connection to sway: {e}");
+                match $self.reconnect().await {
+                    Ok(()) => $self.connection.$method($($arg),*).await,
+                    Err(e) => Err(e),
+                }
+            }
+            result => result,
+        }
+    };
+}
+
+/// A snapshot of a window and its place in the layout.
+#[derive(Clone, Debug)]
+pub struct WindowInfo {
+    /// The container ID of the window.
+    pub id: i64,
+    /// Name of the workspace the window is located on.
+    pub workspace: String,
+    /// Whether the window is floating.
+    pub floating: bool,
+    /// Whether the window has focus.
+    pub focused: bool,
+    /// Whether the window is fullscreen.
+    pub fullscreen: bool,
+    /// The Wayland `app_id` of the window.
+    pub app_id: Option<String>,
+    /// The X11 class of the window.
+    pub class: Option<String>,
+    /// The title of the window.
+    pub title: Option<String>,
+}
+
+impl WindowInfo {
+    fn from_node(node: &Node, workspace: &str) -> Self {
+        let properties = node.window_properties.as_ref();
+        Self {
+            id: node.id,
+            workspace: workspace.to_string(),
+            floating: node.node_type == NodeType::FloatingCon,
+            focused: node.focused,
+            fullscreen: node.fullscreen_mode.is_some_and(|mode| mode > 0),
+            app_id: node.app_id.clone(),
+            class: properties.and_then(|p| p.class.clone()),
+            title: node.name.clone(),
+        }
+    }
+}
+
+/// The place of a window in the layout, used to put the window back after it was stashed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    /// The layout of the parent container, e.g. `tabbed`, `None` for floating windows.
+    pub layout: Option<&'static str>,
+    /// A neighboring window, with `true` if the window comes after it.
+    pub neighbor: Option<(i64, bool)>,
+    /// The position of the window.
+    pub position: (i32, i32),
+    /// The size of the window.
+    pub size: (i32, i32),
+}
+
+impl Placement {
+    /// Captures the placement of the window `id` from the tree.
+    fn from_tree(tree: &Node, id: i64) -> Option<Self> {
+        let (parent, _) = find_parent(tree, id, None)?;
+        let position = |node: &Node| (node.rect.x, node.rect.y);
+        let size = |node: &Node| (node.rect.width, node.rect.height);
+        if let Some(node) = parent.floating_nodes.iter().find(|node| node.id == id) {
+            return Some(Self {
+                layout: None,
+                neighbor: None,
+                position: position(node),
+                size: size(node),
+            });
+        }
+        let index = parent.nodes.iter().position(|node| node.id == id)?;
+        let node = &parent.nodes[index];
+        // Only a window can be a target to move next to, a split container would take the window in
+        let is_view = |node: &&Node| node.app_id.is_some() || node.window_properties.is_some();
+        let before = index
+            .checked_sub(1)
+            .and_then(|index| parent.nodes.get(index))
+            .filter(is_view)
+            .map(|node| (node.id, true));
+        let after = parent
+            .nodes
+            .get(index + 1)
+            .filter(is_view)
+            .map(|node| (node.id, false));
+        let layout = match parent.layout {
+            NodeLayout::SplitH => Some("splith"),
+            NodeLayout::SplitV => Some("splitv"),
+            NodeLayout::Stacked => Some("stacking"),
+            NodeLayout::Tabbed => Some("tabbed"),
+            _ => None,
+        };
+        Some(Self {
+            layout,
+            neighbor: before.or(after),
+            position: position(node),
+            size: size(node),
+        })
+    }
+}
 
 pub struct SwayIpcInterface {
     connection: Connection,
@@ -7,17 +132,336 @@ pub struct SwayIpcInterface {
 
 impl SwayIpcInterface {
     pub async fn new() -> Result<Self, swayipc_async::Error> {
-        let connection = Connection::new().await?;
+        let connection = connect().await?;
         Ok(Self { connection })
     }
 
-    async fn run_command(&mut self, command: &str) -> Result<(), swayipc_async::Error> {
-        self.connection.run_command(command).await?;
+    /// Replaces the connection with a new one, retrying until sway is available again.
+    async fn reconnect(&mut self) -> Result<(), swayipc_async::Error> {
+        let mut attempt = 1;
+        loop {
+            match connect().await {
+                Ok(connection) => {
+                    debug!("Reconnected to sway");
+                    self.connection = connection;
+                    return Ok(());
+                }
+                Err(e) if attempt < RECONNECT_ATTEMPTS => {
+                    debug!("Reconnecting to sway failed (attempt {attempt}): {e}");
+                    attempt += 1;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Runs sway commands. A failing command is reported with the error message from sway.
+    ///
+    /// If the connection was lost, the command is not sent again, as sway may have run it already
+    /// and relative commands like `opacity minus 0.1` would be applied twice. The connection is
+    /// restored for the next call and the error is returned.
+    pub async fn run_command(&mut self, command: &str) -> Result<(), swayipc_async::Error> {
+        trace!("Running sway command: {command}");
+        let outcomes = match self.connection.run_command(command).await {
+            Err(e) if is_disconnect(&e) => {
+                warn!("Lost connection to sway while running `{command}`: {e}");
+                self.reconnect().await?;
+                return Err(e);
+            }
+            result => result?,
+        };
+        for outcome in outcomes {
+            outcome?;
+        }
         Ok(())
     }
 
+    pub async fn get_focused_workspace(&mut self) -> Result<String, swayipc_async::Error> {
+        let workspaces = retry!(self, get_workspaces())?;
+        workspaces
+            .into_iter()
+            .find(|ws| ws.focused)
+            .map(|ws| ws.name)
+            .ok_or_else(|| swayipc_async::Error::CommandFailed("No focused workspace".into()))
+    }
+
+    pub async fn switch_workspace(&mut self, name: &str) -> Result<(), swayipc_async::Error> {
+        debug!("Switching to workspace {name:?}");
+        self.run_command(&format!("workspace {}", quote(name)))
+            .await
+    }
+
+    pub async fn move_focused_to_workspace(
+        &mut self,
+        name: &str,
+    ) -> Result<(), swayipc_async::Error> {
+        debug!("Moving focused window to workspace {name:?}");
+        self.run_command(&format!("move container to workspace {}", quote(name)))
+            .await
+    }
+
+    pub async fn subscribe_window_events() -> Result<EventStream, swayipc_async::Error> {
+        connect().await?.subscribe([EventType::Window]).await
+    }
+
+    /// Returns all windows on regular workspaces, windows in the scratchpad are not included.
+    pub async fn get_windows(&mut self) -> Result<Vec<WindowInfo>, swayipc_async::Error> {
+        let tree = retry!(self, get_tree())?;
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, &mut windows);
+        trace!("Windows: {windows:?}");
+        Ok(windows)
+    }
+
+    pub async fn get_window(
+        &mut self,
+        id: i64,
+    ) -> Result<Option<WindowInfo>, swayipc_async::Error> {
+        Ok(self.get_windows().await?.into_iter().find(|w| w.id == id))
+    }
+
+    pub async fn get_focused_window(&mut self) -> Result<Option<WindowInfo>, swayipc_async::Error> {
+        Ok(self.get_windows().await?.into_iter().find(|w| w.focused))
+    }
+
+    /// Returns the contents of the sway configuration file.
+    pub async fn get_config(&mut self) -> Result<String, swayipc_async::Error> {
+        Ok(retry!(self, get_config())?.config)
+    }
+
+    pub async fn set_focused_colors(
+        &mut self,
+        colors: &[String],
+    ) -> Result<(), swayipc_async::Error> {
+        self.run_command(&format!("client.focused {}", colors.join(" ")))
+            .await
+    }
+
+    pub async fn set_gaps(&mut self, kind: &str, value: i32) -> Result<(), swayipc_async::Error> {
+        self.run_command(&format!("gaps {kind} all set {value}"))
+            .await
+    }
+
+    /// Returns the names of all workspaces.
+    pub async fn get_workspace_names(&mut self) -> Result<Vec<String>, swayipc_async::Error> {
+        let workspaces = retry!(self, get_workspaces())?;
+        Ok(workspaces.into_iter().map(|ws| ws.name).collect())
+    }
+
+    /// Sets the gaps of single workspaces. Sway only sets the gaps of the focused workspace, so
+    /// each workspace is focused in turn. The visible and the focused workspaces are shown again
+    /// afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of the gaps, `inner` or `outer`.
+    /// * `gaps` - The names of the workspaces with their gaps.
+    pub async fn set_workspace_gaps(
+        &mut self,
+        kind: &str,
+        gaps: &[(String, i32)],
+    ) -> Result<(), swayipc_async::Error> {
+        let workspaces = retry!(self, get_workspaces())?;
+        let mut commands: Vec<String> = gaps
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "workspace --no-auto-back-and-forth {}; gaps {kind} current set {value}",
+                    quote(name)
+                )
+            })
+            .collect();
+        let visible = workspaces.iter().filter(|ws| ws.visible && !ws.focused);
+        let focused = workspaces.iter().filter(|ws| ws.focused);
+        commands.extend(
+            visible
+                .chain(focused)
+                .map(|ws| format!("workspace --no-auto-back-and-forth {}", quote(&ws.name))),
+        );
+        self.run_command(&commands.join("; ")).await
+    }
+
+    /// Changes the opacity of a window relative to its current opacity. Sway fails if the opacity
+    /// would leave the range between 0.0 and 1.0.
+    pub async fn change_opacity(
+        &mut self,
+        id: i64,
+        delta: f64,
+    ) -> Result<(), swayipc_async::Error> {
+        let change = if delta < 0.0 { "minus" } else { "plus" };
+        self.run_command(&format!("[con_id={id}] opacity {change} {}", delta.abs()))
+            .await
+    }
+
+    pub async fn set_fullscreen(
+        &mut self,
+        id: i64,
+        enable: bool,
+    ) -> Result<(), swayipc_async::Error> {
+        let state = if enable { "enable" } else { "disable" };
+        self.run_command(&format!("[con_id={id}] fullscreen {state}"))
+            .await
+    }
+
+    pub async fn get_binding_mode(&mut self) -> Result<String, swayipc_async::Error> {
+        retry!(self, get_binding_state())
+    }
+
+    pub async fn get_binding_modes(&mut self) -> Result<Vec<String>, swayipc_async::Error> {
+        retry!(self, get_binding_modes())
+    }
+
+    pub async fn set_binding_mode(&mut self, mode: &str) -> Result<(), swayipc_async::Error> {
+        debug!("Switching to binding mode {mode:?}");
+        self.run_command(&format!("mode {}", quote(mode))).await
+    }
+
+    /// Binds `key` in the current binding mode, replacing an existing binding without a warning.
+    pub async fn bindsym(
+        &mut self,
+        flags: &[String],
+        key: &str,
+        command: &str,
+    ) -> Result<(), swayipc_async::Error> {
+        debug!("Binding {key} {flags:?} to {command:?}");
+        let mut flags = flags.to_vec();
+        flags.push("--no-warn".to_string());
+        self.run_command(&format!("bindsym {} {key} {command}", flags.join(" ")))
+            .await
+    }
+
+    pub async fn unbindsym(&mut self, key: &str) -> Result<(), swayipc_async::Error> {
+        debug!("Removing binding {key}");
+        self.run_command(&format!("unbindsym {key}")).await
+    }
+
+    /// Returns the name, enabled and power state of every output.
+    pub async fn get_outputs(&mut self) -> Result<Vec<(String, bool, bool)>, swayipc_async::Error> {
+        let outputs = retry!(self, get_outputs())?;
+        trace!("Outputs: {outputs:?}");
+        Ok(outputs
+            .into_iter()
+            .map(|output| (output.name, output.active, output.dpms))
+            .collect())
+    }
+
+    pub async fn set_output_enabled(
+        &mut self,
+        name: &str,
+        enable: bool,
+    ) -> Result<(), swayipc_async::Error> {
+        let state = if enable { "enable" } else { "disable" };
+        debug!("Output {name}: {state}");
+        self.run_command(&format!("output {} {state}", quote(name)))
+            .await
+    }
+
+    pub async fn set_output_power(
+        &mut self,
+        name: &str,
+        on: bool,
+    ) -> Result<(), swayipc_async::Error> {
+        let state = if on { "on" } else { "off" };
+        debug!("Output {name}: power {state}");
+        self.run_command(&format!("output {} power {state}", quote(name)))
+            .await
+    }
+
+    pub async fn focus_window(&mut self, id: i64) -> Result<(), swayipc_async::Error> {
+        self.run_command(&format!("[con_id={id}] focus")).await
+    }
+
+    pub async fn move_window_to_scratchpad(&mut self, id: i64) -> Result<(), swayipc_async::Error> {
+        self.run_command(&format!("[con_id={id}] move scratchpad"))
+            .await
+    }
+
+    pub async fn move_window_to_workspace(
+        &mut self,
+        id: i64,
+        workspace: &str,
+    ) -> Result<(), swayipc_async::Error> {
+        self.run_command(&format!(
+            "[con_id={id}] move container to workspace {}",
+            quote(workspace)
+        ))
+        .await
+    }
+
+    /// Returns the placement of a window in the layout.
+    pub async fn get_placement(
+        &mut self,
+        id: i64,
+    ) -> Result<Option<Placement>, swayipc_async::Error> {
+        let tree = retry!(self, get_tree())?;
+        Ok(Placement::from_tree(&tree, id))
+    }
+
+    /// Puts a window, which is already back on its workspace, to its place in the layout. A
+    /// floating window gets its position and size back. A tiled window is moved next to its
+    /// neighbor, if the neighbor is on a workspace, and gets the layout of its parent and its size
+    /// back.
+    pub async fn restore_placement(
+        &mut self,
+        id: i64,
+        placement: &Placement,
+    ) -> Result<(), swayipc_async::Error> {
+        let (x, y) = placement.position;
+        let (width, height) = placement.size;
+        let Some(layout) = placement.layout else {
+            return self
+                .run_command(&format!(
+                    "[con_id={id}] move position {x} px {y} px, resize set {width} px {height} px"
+                ))
+                .await;
+        };
+        let tree = retry!(self, get_tree())?;
+        let mut commands = Vec::new();
+        if let Some((neighbor, after)) = placement.neighbor {
+            let on_workspace = find_parent(&tree, neighbor, None)
+                .is_some_and(|(_, workspace)| workspace.is_some_and(|ws| ws != "__i3_scratch"));
+            if on_workspace {
+                commands.push(format!(
+                    "[con_id={neighbor}] mark --add {RESTORE_MARK}; \
+                     [con_id={id}] move container to mark {RESTORE_MARK}; \
+                     [con_id={neighbor}] unmark {RESTORE_MARK}"
+                ));
+                // Moving to a mark places the window after the marked one
+                if !after {
+                    commands.push(format!(
+                        "[con_id={id}] swap container with con_id {neighbor}"
+                    ));
+                }
+            } else {
+                debug!("Neighbor {neighbor} of window {id} is gone, not moving next to it");
+            }
+        }
+        commands.push(format!("[con_id={id}] layout {layout}"));
+        commands.push(format!(
+            "[con_id={id}] resize set width {width} px height {height} px"
+        ));
+        self.run_command(&commands.join("; ")).await
+    }
+
+    /// Takes a window out of the scratchpad and places it on `workspace` again.
+    pub async fn restore_window_from_scratchpad(
+        &mut self,
+        id: i64,
+        workspace: &str,
+        floating: bool,
+    ) -> Result<(), swayipc_async::Error> {
+        // Disabling floating removes the window from the scratchpad for good
+        let floating = if floating { ", floating enable" } else { "" };
+        self.run_command(&format!(
+            "[con_id={id}] scratchpad show, floating disable{floating}, move container to workspace {}",
+            quote(workspace)
+        ))
+        .await
+    }
+
     pub async fn set_bars_invisible(&mut self) -> Result<(), swayipc_async::Error> {
-        let ids = self.connection.get_bar_ids().await?;
+        let ids = retry!(self, get_bar_ids())?;
         debug!("Setting bars invisible: {ids:?}");
         for id in ids {
             self.set_bar_mode(&id, BarMode::Invisible).await?;
@@ -34,11 +478,48 @@ impl SwayIpcInterface {
             .await
     }
 
+    /// Returns the `status_command` of every bar, `None` if a bar has no status command.
+    pub async fn get_status_commands(
+        &mut self,
+    ) -> Result<Vec<(String, Option<String>)>, swayipc_async::Error> {
+        let ids = retry!(self, get_bar_ids())?;
+        let mut commands = Vec::new();
+        for id in ids {
+            // Sway omits the status command if there is none, which fails to parse
+            let command = match retry!(self, get_bar_config(id.clone())) {
+                Ok(bar_config) => Some(bar_config.status_command),
+                Err(swayipc_async::Error::SerdeJson(e))
+                    if e.to_string().contains("missing field `status_command`") =>
+                {
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+            commands.push((id, command));
+        }
+        trace!("List of status commands: {commands:?}");
+        Ok(commands)
+    }
+
+    pub async fn set_status_command(
+        &mut self,
+        bar_id: &str,
+        command: Option<&str>,
+    ) -> Result<(), swayipc_async::Error> {
+        debug!("Setting status command for {bar_id} to {command:?}");
+        // A single dash removes the status command
+        self.run_command(&format!(
+            "bar {bar_id} status_command {}",
+            command.map_or_else(|| "-".to_string(), quote)
+        ))
+        .await
+    }
+
     pub async fn get_bar_mode(&mut self) -> Option<Vec<(String, BarMode)>> {
-        let ids = self.connection.get_bar_ids().await.ok()?;
+        let ids = retry!(self, get_bar_ids()).ok()?;
         let mut bar_modes = Vec::new();
         for id in ids {
-            let bar_config = self.connection.get_bar_config(id.clone()).await.ok()?;
+            let bar_config = retry!(self, get_bar_config(id.clone())).ok()?;
             bar_modes.push((id, bar_config.mode));
         }
         trace!("List of bar modes: {bar_modes:?}");
@@ -53,7 +534,7 @@ impl SwayIpcInterface {
             Some(modes) => modes,
             None => {
                 debug!("No previous bar modes provided, defaulting all bars to Dock mode");
-                let ids = self.connection.get_bar_ids().await?;
+                let ids = retry!(self, get_bar_ids())?;
                 ids.into_iter().map(|id| (id, BarMode::Dock)).collect()
             }
         };
@@ -76,3 +557,348 @@ impl SwayIpcInterface {
         Ok(())
     }
 }
+
+/// Runs an event watcher in the background. The watcher is started again whenever it returns,
+/// which happens if the connection to sway was lost, so it subscribes to the new sway instance.
+///
+/// # Arguments
+///
+/// * `name` - Name of the watcher for the log.
+/// * `watch` - Creates the future which subscribes to the events and handles them.
+///
+/// # Returns
+///
+/// The handle of the background task, which has to be aborted at the end of the session.
+pub fn spawn_watcher<F, Fut>(name: &'static str, watch: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send,
+{
+    tokio::spawn(async move {
+        loop {
+            match watch().await {
+                Ok(()) => debug!("Event stream for {name} ended, subscribing again"),
+                Err(e) => warn!("Watching {name} failed, subscribing again: {e}"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+/// Connects to the sway IPC socket.
+///
+/// `SWAYSOCK` is tried first. After sway crashed or was restarted, the socket it points to is gone
+/// or left behind without a listener, and sway listens on a new socket, which is named after its
+/// PID. In that case, the newest socket of a running sway process in the runtime directory is used.
+async fn connect() -> Result<Connection, swayipc_async::Error> {
+    if let Some(path) = std::env::var_os("SWAYSOCK").map(PathBuf::from) {
+        trace!("Connecting to sway at {path:?}");
+        match Async::<UnixStream>::connect(&path).await {
+            Ok(stream) => return Ok(Connection::from(stream)),
+            Err(e) => {
+                debug!("Connecting to SWAYSOCK {path:?} failed, looking for a new socket: {e}")
+            }
+        }
+    }
+    let path = find_socket()?;
+    trace!("Connecting to sway at {path:?}");
+    Ok(Connection::from(Async::<UnixStream>::connect(path).await?))
+}
+
+/// Checks whether an error means that the connection to sway is gone.
+fn is_disconnect(error: &swayipc_async::Error) -> bool {
+    matches!(error, swayipc_async::Error::Io(_))
+}
+
+/// Finds the newest socket of a running sway process in the runtime directory.
+fn find_socket() -> Result<PathBuf, swayipc_async::Error> {
+    let uid = unsafe { libc::getuid() };
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("/run/user/{uid}")));
+    std::fs::read_dir(&runtime_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| socket_pid(name, uid))
+                .is_some_and(|pid| PathBuf::from(format!("/proc/{pid}")).exists())
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+        .ok_or(swayipc_async::Error::SocketNotFound)
+}
+
+/// Extracts the PID from the name of a sway socket, `sway-ipc.<uid>.<pid>.sock`.
+fn socket_pid(name: &str, uid: u32) -> Option<u32> {
+    name.strip_prefix(&format!("sway-ipc.{uid}."))?
+        .strip_suffix(".sock")?
+        .parse()
+        .ok()
+}
+
+/// Finds the parent of the container `id` below `node`, together with the name of the workspace
+/// the container is on.
+fn find_parent<'a>(
+    node: &'a Node,
+    id: i64,
+    workspace: Option<&'a str>,
+) -> Option<(&'a Node, Option<&'a str>)> {
+    let workspace = match node.node_type {
+        NodeType::Workspace => node.name.as_deref(),
+        _ => workspace,
+    };
+    let children = node.nodes.iter().chain(node.floating_nodes.iter());
+    if children.clone().any(|child| child.id == id) {
+        return Some((node, workspace));
+    }
+    children
+        .into_iter()
+        .find_map(|child| find_parent(child, id, workspace))
+}
+
+/// Collects all windows below `node`. Windows are containers without children, which have either
+/// an `app_id` or X11 window properties.
+fn collect_windows(node: &Node, workspace: Option<&str>, windows: &mut Vec<WindowInfo>) {
+    let workspace = match node.node_type {
+        NodeType::Workspace => node.name.as_deref(),
+        _ => workspace,
+    };
+    if workspace == Some("__i3_scratch") {
+        return;
+    }
+    let is_view = node.app_id.is_some() || node.window_properties.is_some();
+    if let (Some(workspace), true) = (workspace, is_view) {
+        windows.push(WindowInfo::from_node(node, workspace));
+    }
+    for child in node.nodes.iter().chain(node.floating_nodes.iter()) {
+        collect_windows(child, workspace, windows);
+    }
+}
+
+/// Returns the lines of the sway config outside of blocks like `bar { ... }`.
+pub fn top_level_lines(sway_config: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0usize;
+    sway_config.lines().filter(move |line| {
+        let line = line.trim();
+        if line.starts_with('#') {
+            return false;
+        }
+        let top_level = depth == 0 && !line.ends_with('{');
+        if line.ends_with('{') {
+            depth += 1;
+        } else if line == "}" {
+            depth = depth.saturating_sub(1);
+        }
+        top_level
+    })
+}
+
+/// Finds the last line at the top level of the sway config starting with the `key` words and
+/// returns the remaining words of that line.
+pub fn config_value(sway_config: &str, key: &[&str]) -> Option<Vec<String>> {
+    let lines: Vec<&str> = top_level_lines(sway_config).collect();
+    lines.into_iter().rev().find_map(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        words
+            .starts_with(key)
+            .then(|| words[key.len()..].iter().map(|w| w.to_string()).collect())
+    })
+}
+
+/// Quotes an argument for a sway command, so that names with spaces or quotes are passed as is.
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A stand-in sway for tests, on the other end of a socket pair.
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// The type of the `RUN_COMMAND` message.
+    const RUN_COMMAND: u32 = 0;
+    /// The type of the `GET_TREE` message.
+    pub const GET_TREE: u32 = 4;
+    /// The type of the `GET_BINDING_MODES` message.
+    pub const GET_BINDING_MODES: u32 = 8;
+    /// The type of the `GET_CONFIG` message.
+    pub const GET_CONFIG: u32 = 9;
+    /// The type of the `GET_BINDING_STATE` message.
+    pub const GET_BINDING_STATE: u32 = 12;
+
+    /// Answers messages with fixed replies and records the commands it runs. A command which
+    /// contains one of the failing texts fails, the other commands succeed.
+    #[derive(Default)]
+    pub struct FakeSway {
+        replies: HashMap<u32, serde_json::Value>,
+        failing: Vec<String>,
+    }
+
+    impl FakeSway {
+        /// Sets the reply to a type of message, e.g. [`GET_TREE`].
+        pub fn reply(mut self, message_type: u32, reply: serde_json::Value) -> Self {
+            self.replies.insert(message_type, reply);
+            self
+        }
+
+        /// Lets the commands which contain a text fail.
+        pub fn fail(mut self, command: &str) -> Self {
+            self.failing.push(command.to_string());
+            self
+        }
+
+        /// Starts answering in the background.
+        ///
+        /// # Returns
+        ///
+        /// The interface connected to the fake, and the commands it has run so far.
+        pub fn start(self) -> (SwayIpcInterface, Arc<Mutex<Vec<String>>>) {
+            let (server, client) = UnixStream::pair().unwrap();
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&commands);
+            std::thread::spawn(move || self.serve(server, &recorded));
+            let connection = Connection::from(Async::new(client).unwrap());
+            (SwayIpcInterface { connection }, commands)
+        }
+
+        fn serve(&self, mut stream: UnixStream, commands: &Mutex<Vec<String>>) {
+            // `i3-ipc`, the length of the payload and the type of the message
+            let mut header = [0u8; 14];
+            while stream.read_exact(&mut header).is_ok() {
+                let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+                let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+                let mut payload = vec![0; length as usize];
+                stream.read_exact(&mut payload).unwrap();
+                let reply = match message_type {
+                    RUN_COMMAND => {
+                        let command = String::from_utf8(payload).unwrap();
+                        let failed = self.failing.iter().any(|text| command.contains(text));
+                        commands.lock().unwrap().push(command);
+                        if failed {
+                            json!([{ "success": false, "parse_error": false, "error": "Failed" }])
+                        } else {
+                            json!([{ "success": true }])
+                        }
+                    }
+                    _ => self
+                        .replies
+                        .get(&message_type)
+                        .cloned()
+                        .unwrap_or(json!([])),
+                }
+                .to_string();
+                header[6..10].copy_from_slice(&(reply.len() as u32).to_ne_bytes());
+                if stream.write_all(&header).is_err() || stream.write_all(reply.as_bytes()).is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Builds a node of the tree as sway reports it.
+    pub fn node(
+        id: i64,
+        node_type: &str,
+        layout: &str,
+        nodes: Vec<serde_json::Value>,
+        floating_nodes: Vec<serde_json::Value>,
+    ) -> serde_json::Value {
+        let rect = serde_json::json!({ "x": id, "y": 0, "width": 100 * id, "height": 50 });
+        serde_json::json!({
+            "id": id,
+            "name": format!("{id}"),
+            "type": node_type,
+            "border": "none",
+            "current_border_width": 0,
+            "layout": layout,
+            "rect": rect,
+            "window_rect": rect,
+            "deco_rect": rect,
+            "geometry": rect,
+            "urgent": false,
+            "focused": false,
+            "focus": [],
+            "nodes": nodes,
+            "floating_nodes": floating_nodes,
+            "sticky": false,
+            "app_id": (node_type != "workspace").then_some("foot"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::node;
+    use super::*;
+
+    const CONFIG: &str = "
+set $mod Mod4
+gaps inner 8
+bar {
+    gaps 4
+}
+mode \"resize\" {
+    gaps outer 3
+}
+workspace 1 gaps inner 2
+";
+
+    #[test]
+    fn test_socket_pid() {
+        assert_eq!(socket_pid("sway-ipc.1000.1234.sock", 1000), Some(1234));
+        assert_eq!(socket_pid("sway-ipc.1000.1234.sock", 1001), None);
+        assert_eq!(socket_pid("sway-ipc.1000.sock", 1000), None);
+        assert_eq!(socket_pid("wayland-1", 1000), None);
+    }
+
+    #[test]
+    fn test_placement() {
+        let tree: Node = serde_json::from_value(node(
+            1,
+            "workspace",
+            "tabbed",
+            vec![
+                node(2, "con", "none", vec![], vec![]),
+                node(3, "con", "none", vec![], vec![]),
+            ],
+            vec![node(4, "floating_con", "none", vec![], vec![])],
+        ))
+        .unwrap();
+        assert_eq!(
+            Placement::from_tree(&tree, 2),
+            Some(Placement {
+                layout: Some("tabbed"),
+                neighbor: Some((3, false)),
+                position: (2, 0),
+                size: (200, 50),
+            })
+        );
+        assert_eq!(
+            Placement::from_tree(&tree, 3).unwrap().neighbor,
+            Some((2, true))
+        );
+        let floating = Placement::from_tree(&tree, 4).unwrap();
+        assert_eq!((floating.layout, floating.neighbor), (None, None));
+        assert_eq!(Placement::from_tree(&tree, 5), None);
+    }
+
+    #[test]
+    fn test_config_value() {
+        assert_eq!(
+            config_value(CONFIG, &["gaps", "inner"]),
+            Some(vec!["8".to_string()])
+        );
+        assert_eq!(config_value(CONFIG, &["gaps", "outer"]), None);
+        assert_eq!(
+            config_value(CONFIG, &["set", "$mod"]),
+            Some(vec!["Mod4".to_string()])
+        );
+    }
+}
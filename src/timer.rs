@@ -1,11 +1,13 @@
+use crate::session::{Phase, SessionState};
+
 use std::io::Write;
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::watch;
 
 use log::debug;
 
 /// A simple timer struct that tracks a duration and start time.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Timer {
     duration: Duration,
     start: Instant,
@@ -62,6 +64,18 @@ impl Timer {
         }
     }
 
+    /// Pauses the timer if it's running, as if it had been paused at the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - The time at which the pause started, not after now.
+    pub fn pause_since(&mut self, since: Instant) {
+        if !self.is_paused {
+            self.paused_time = since.saturating_duration_since(self.start);
+            self.is_paused = true;
+        }
+    }
+
     /// Resumes the timer if it's paused.
     pub fn resume(&mut self) {
         if self.is_paused {
@@ -70,15 +84,6 @@ impl Timer {
         }
     }
 
-    /// Toggles the timer between paused and running states.
-    pub fn toggle_pause(&mut self) {
-        if self.is_paused {
-            self.resume();
-        } else {
-            self.pause();
-        }
-    }
-
     /// Returns whether the timer is currently paused.
     pub fn is_paused(&self) -> bool {
         self.is_paused
@@ -145,6 +150,30 @@ impl Timer {
     }
 }
 
+impl Timer {
+    /// Calculates how long it takes until the adapted format of the remaining time changes.
+    ///
+    /// # Returns
+    ///
+    /// The duration until [`Timer::remaining_str_adapted_format`] shows a different time. It is
+    /// unlimited while the timer is paused.
+    pub fn until_adapted_format_changes(&self) -> Option<Duration> {
+        if self.is_paused {
+            return None;
+        }
+        let remaining = self.remaining();
+        let granularity = match remaining.as_secs() {
+            3600.. => 1,
+            60.. => 30,
+            10.. => 10,
+            _ => 1,
+        };
+        let shown = Duration::from_secs(remaining.as_secs() / granularity * granularity);
+        // Wake up just after the boundary, so that the new value is shown
+        Some(remaining - shown + Duration::from_millis(1))
+    }
+}
+
 impl std::fmt::Display for Timer {
     /// Formats the timer for display.
     ///
@@ -166,30 +195,41 @@ impl std::fmt::Display for Timer {
 
 /// Displays a countdown timer in the terminal with pause support.
 ///
-/// This function shows the remaining time and updates based on pause state.
-/// The display updates every second and shows "(PAUSED)" when the timer is paused.
+/// This function shows the remaining time and updates when the session state changes, or when the
+/// shown time changes. It shows "(PAUSED)" when the timer is paused.
 ///
 /// # Arguments
 ///
-/// * `timer` - Arc<Mutex<Timer>> to display with pause support.
-pub async fn print_remaining_time_with_pause(timer: Arc<Mutex<Timer>>) {
+/// * `state` - The receiver of the session state to display.
+pub async fn print_remaining_time_with_pause(mut state: watch::Receiver<SessionState>) {
     print!("\x1B[?25l"); // Hide cursor
     loop {
-        let (remaining, is_paused) = {
-            let timer_guard = timer.lock().unwrap();
-            if !timer_guard.is_remaining() {
+        let timer = {
+            let state = state.borrow_and_update();
+            let over = matches!(
+                state.phase,
+                Phase::Overtime | Phase::Finishing | Phase::Finished(_)
+            );
+            if over || !state.timer.is_remaining() {
                 break;
             }
-            (
-                timer_guard.remaining_str_adapted_format(),
-                timer_guard.is_paused(),
-            )
+            state.timer
         };
 
-        let status = if is_paused { " (PAUSED)" } else { "" };
+        let status = if timer.is_paused() { " (PAUSED)" } else { "" };
+        let remaining = timer.remaining_str_adapted_format();
         print!("\x1B[2K\rTime remaining: {remaining}{status}");
         std::io::stdout().flush().unwrap();
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let tick = timer.until_adapted_format_changes();
+        tokio::select! {
+            changed = state.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            },
+            _ = tokio::time::sleep(tick.unwrap_or_default()), if tick.is_some() => {},
+        }
     }
     print!("\x1B[?25h"); // Show cursor
 }
@@ -0,0 +1,382 @@
+use crate::config::VisualConfig;
+use crate::sway_ipc_interface::{self, config_value, top_level_lines, SwayIpcInterface};
+
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
+use log::{debug, warn};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use swayipc_async::{Event, WindowChange};
+use tokio::task::JoinHandle;
+
+/// The `client.focused` colors sway uses if the config does not set them: border, background,
+/// text, indicator and child border.
+const DEFAULT_FOCUSED_COLORS: [&str; 5] = ["#4c7899", "#285577", "#ffffff", "#2e9ef4", "#285577"];
+
+/// The original values of everything changed by [`Visual::apply`], used to revert the changes.
+pub struct VisualSession {
+    focused_colors: Option<Vec<String>>,
+    /// The kind of gaps with the original gaps of every workspace.
+    gaps: Vec<(&'static str, Vec<(String, i32)>)>,
+    opacity_task: Option<JoinHandle<()>>,
+    /// How much the opacity of the unfocused windows is lowered.
+    dim: f64,
+    /// The windows whose opacity is currently lowered.
+    dimmed: Arc<Mutex<HashSet<i64>>>,
+    fullscreen_window: Option<i64>,
+}
+
+/// Visual cues inside sway, which indicate that a focus session is running.
+#[derive(Clone, Debug)]
+pub struct Visual {
+    config: VisualConfig,
+}
+
+impl Visual {
+    /// Creates a new `Visual` from its configuration.
+    pub fn new(config: VisualConfig) -> Self {
+        Self { config }
+    }
+
+    /// Captures the current values and applies the configured visual changes.
+    ///
+    /// Sway does not report the colors and gaps over IPC directly, so they are read from the
+    /// top level of the config returned by IPC, falling back to the sway defaults if they are not
+    /// set there. The gaps are captured for every workspace. The opacity of the unfocused windows
+    /// is lowered relative to their own opacity, so that it can be raised by the same amount
+    /// afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `VisualSession` to revert the changes with.
+    pub async fn apply(&self, sway: &mut SwayIpcInterface) -> Result<VisualSession> {
+        let sway_config = sway.get_config().await?;
+        let mut session = VisualSession {
+            focused_colors: None,
+            gaps: Vec::new(),
+            opacity_task: None,
+            dim: 0.0,
+            dimmed: Arc::default(),
+            fullscreen_window: None,
+        };
+
+        if let Err(e) = self.apply_changes(sway, &sway_config, &mut session).await {
+            if let Err(revert_error) = session.revert(sway).await {
+                warn!("Failed to revert the visual changes: {revert_error:#}");
+            }
+            return Err(e);
+        }
+        Ok(session)
+    }
+
+    /// Applies the configured changes, recording each original value in the session before the
+    /// change is made, so that a failure halfway can be reverted.
+    async fn apply_changes(
+        &self,
+        sway: &mut SwayIpcInterface,
+        sway_config: &str,
+        session: &mut VisualSession,
+    ) -> Result<()> {
+        if let Some(color) = &self.config.focused_border {
+            let original = focused_colors(sway_config);
+            let mut colors = original.clone();
+            // Border and child border, the latter is the visible part with pixel borders
+            colors[0] = color.clone();
+            colors[4] = color.clone();
+            session.focused_colors = Some(original);
+            sway.set_focused_colors(&colors).await?;
+        }
+
+        for (kind, value) in [
+            ("inner", self.config.gaps_inner),
+            ("outer", self.config.gaps_outer),
+        ] {
+            if let Some(value) = value {
+                let workspaces = sway.get_workspace_names().await?;
+                let original = workspace_gaps(sway_config, kind, workspaces);
+                session.gaps.push((kind, original));
+                sway.set_gaps(kind, value).await?;
+            }
+        }
+
+        if self.config.fullscreen.unwrap_or(false) {
+            match sway.get_focused_window().await? {
+                Some(window) if !window.fullscreen => {
+                    session.fullscreen_window = Some(window.id);
+                    sway.set_fullscreen(window.id, true).await?;
+                }
+                Some(_) => debug!("Focused window is already fullscreen"),
+                None => debug!("No focused window to make fullscreen"),
+            }
+        }
+
+        if let Some(opacity) = self.config.unfocused_opacity {
+            let dim = 1.0 - opacity;
+            session.dim = dim;
+            for window in sway.get_windows().await? {
+                if !window.focused {
+                    dim_window(sway, &session.dimmed, window.id, dim).await;
+                }
+            }
+            let dimmed = Arc::clone(&session.dimmed);
+            session.opacity_task = Some(sway_ipc_interface::spawn_watcher(
+                "unfocused windows to dim",
+                move || dim_unfocused(dim, Arc::clone(&dimmed)),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl VisualSession {
+    /// Reverts all visual changes. The dimmed windows get their own opacity back, and every
+    /// workspace its own gaps. Every change is reverted even if reverting another one fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `sway` - The sway IPC interface.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success or failure of the operation.
+    pub async fn revert(self, sway: &mut SwayIpcInterface) -> Result<()> {
+        let mut failures = Vec::new();
+        if let Some(task) = self.opacity_task {
+            task.abort();
+        }
+        let dimmed = std::mem::take(&mut *self.dimmed.lock().unwrap());
+        if !dimmed.is_empty() {
+            match sway.get_windows().await {
+                // Closed windows cannot be restored
+                Ok(windows) => {
+                    for window in windows.iter().filter(|w| dimmed.contains(&w.id)) {
+                        if let Err(e) = sway.change_opacity(window.id, self.dim).await {
+                            failures.push(format!("opacity of window {}: {e}", window.id));
+                        }
+                    }
+                }
+                Err(e) => failures.push(format!("opacity: {e}")),
+            }
+        }
+        if let Some(id) = self.fullscreen_window {
+            if let Err(e) = sway.set_fullscreen(id, false).await {
+                failures.push(format!("fullscreen of window {id}: {e}"));
+            }
+        }
+        for (kind, gaps) in self.gaps {
+            if let Err(e) = sway.set_workspace_gaps(kind, &gaps).await {
+                failures.push(format!("{kind} gaps: {e}"));
+            }
+        }
+        if let Some(colors) = self.focused_colors {
+            if let Err(e) = sway.set_focused_colors(&colors).await {
+                failures.push(format!("focused colors: {e}"));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Failed to revert the visual changes: {}",
+                failures.join("; ")
+            ))
+        }
+    }
+}
+
+/// Lowers the opacity of a window, unless it is already dimmed. A failure is only logged, e.g.
+/// for a window whose opacity is lower than the amount to dim it by.
+async fn dim_window(sway: &mut SwayIpcInterface, dimmed: &Mutex<HashSet<i64>>, id: i64, dim: f64) {
+    if dimmed.lock().unwrap().contains(&id) {
+        return;
+    }
+    match sway.change_opacity(id, -dim).await {
+        Ok(()) => {
+            dimmed.lock().unwrap().insert(id);
+        }
+        Err(e) => debug!("Not dimming window {id}: {e}"),
+    }
+}
+
+/// Restores the opacity of the focused window and dims the window that lost focus.
+async fn dim_unfocused(dim: f64, dimmed: Arc<Mutex<HashSet<i64>>>) -> Result<()> {
+    let mut events = SwayIpcInterface::subscribe_window_events().await?;
+    let mut sway = SwayIpcInterface::new().await?;
+    let mut previous = sway.get_focused_window().await?.map(|w| w.id);
+    while let Some(event) = events.next().await {
+        let Event::Window(event) = event? else {
+            continue;
+        };
+        if event.change != WindowChange::Focus {
+            continue;
+        }
+        let id = event.container.id;
+        if let Some(previous) = previous.filter(|previous| *previous != id) {
+            // The previous window might have been closed, which is not an error here
+            dim_window(&mut sway, &dimmed, previous, dim).await;
+        }
+        let was_dimmed = dimmed.lock().unwrap().remove(&id);
+        if was_dimmed {
+            if let Err(e) = sway.change_opacity(id, dim).await {
+                warn!("Failed to restore the opacity of window {id}: {e}");
+            }
+        }
+        previous = Some(id);
+    }
+    Ok(())
+}
+
+/// Returns the gaps of every workspace from the top level of the sway config, either from a
+/// `workspace <name> gaps` line or the default `gaps` line.
+///
+/// # Arguments
+///
+/// * `sway_config` - The sway config.
+/// * `kind` - The kind of the gaps, `inner` or `outer`.
+/// * `workspaces` - The names of the workspaces.
+fn workspace_gaps(sway_config: &str, kind: &str, workspaces: Vec<String>) -> Vec<(String, i32)> {
+    let default = config_value(sway_config, &["gaps", kind])
+        .and_then(|values| values.first()?.parse().ok())
+        .unwrap_or(0);
+    let mut configured = Vec::new();
+    for line in top_level_lines(sway_config) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let ["workspace", name @ .., "gaps", gaps_kind, value] = words.as_slice() else {
+            continue;
+        };
+        if *gaps_kind == kind {
+            if let Ok(value) = value.parse::<i32>() {
+                configured.push((name.join(" ").trim_matches('"').to_string(), value));
+            }
+        }
+    }
+    workspaces
+        .into_iter()
+        .map(|name| {
+            let value = configured
+                .iter()
+                .rev()
+                .find(|(configured, _)| *configured == name)
+                .map_or(default, |(_, value)| *value);
+            (name, value)
+        })
+        .collect()
+}
+
+/// Returns the `client.focused` colors from the sway config, completed with the defaults.
+fn focused_colors(sway_config: &str) -> Vec<String> {
+    let mut colors: Vec<String> = DEFAULT_FOCUSED_COLORS.map(String::from).to_vec();
+    if let Some(values) = config_value(sway_config, &["client.focused"]) {
+        // Sway uses the background color as child border if it is not given
+        if values.len() < 5 {
+            if let Some(background) = values.get(1) {
+                colors[4] = background.clone();
+            }
+        }
+        for (color, value) in colors.iter_mut().zip(values) {
+            *color = value;
+        }
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sway_ipc_interface::fake::{self, FakeSway};
+
+    const CONFIG: &str = "
+set $mod Mod4
+gaps inner 8
+client.focused #111111 #222222 #333333
+";
+
+    #[test]
+    fn test_focused_colors() {
+        assert_eq!(
+            focused_colors(CONFIG),
+            vec!["#111111", "#222222", "#333333", "#2e9ef4", "#222222"]
+        );
+        assert_eq!(focused_colors(""), DEFAULT_FOCUSED_COLORS.to_vec());
+    }
+
+    #[test]
+    fn test_workspace_gaps() {
+        let config = "
+gaps inner 8
+workspace \"2: web\" gaps inner 0
+workspace 3 gaps outer 4
+bar {
+    gaps 4
+}
+";
+        let workspaces = vec!["1".to_string(), "2: web".to_string(), "3".to_string()];
+        assert_eq!(
+            workspace_gaps(config, "inner", workspaces.clone()),
+            vec![
+                ("1".to_string(), 8),
+                ("2: web".to_string(), 0),
+                ("3".to_string(), 8)
+            ]
+        );
+        assert_eq!(
+            workspace_gaps(config, "outer", workspaces)[2],
+            ("3".to_string(), 4)
+        );
+    }
+
+    /// A workspace with the focused window 2.
+    fn tree() -> serde_json::Value {
+        let mut window = fake::node(2, "con", "none", vec![], vec![]);
+        window["focused"] = serde_json::json!(true);
+        fake::node(1, "workspace", "splith", vec![window], vec![])
+    }
+
+    #[tokio::test]
+    async fn test_apply_fails_halfway() {
+        let (mut sway, commands) = FakeSway::default()
+            .reply(fake::GET_CONFIG, serde_json::json!({ "config": CONFIG }))
+            .reply(fake::GET_TREE, tree())
+            .fail("fullscreen enable")
+            .start();
+        let visual = Visual::new(VisualConfig {
+            focused_border: Some("#d79921".to_string()),
+            fullscreen: Some(true),
+            ..VisualConfig::default()
+        });
+        assert!(visual.apply(&mut sway).await.is_err());
+
+        // The colors changed before the failure are reverted
+        assert_eq!(
+            commands.lock().unwrap().last().unwrap(),
+            "client.focused #111111 #222222 #333333 #2e9ef4 #222222"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_every_change() {
+        let (mut sway, commands) = FakeSway::default().fail("fullscreen disable").start();
+        let colors = focused_colors(CONFIG);
+        let session = VisualSession {
+            focused_colors: Some(colors.clone()),
+            gaps: Vec::new(),
+            opacity_task: None,
+            dim: 0.0,
+            dimmed: Arc::default(),
+            fullscreen_window: Some(2),
+        };
+        assert!(session.revert(&mut sway).await.is_err());
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "[con_id=2] fullscreen disable".to_string(),
+                format!("client.focused {}", colors.join(" "))
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use crate::config::WindowRuleConfig;
+use crate::sway_ipc_interface::WindowInfo;
+
+use anyhow::Result;
+use regex::Regex;
+
+/// A rule that matches windows by their `app_id`, X11 class and title.
+///
+/// Every specified property is a regular expression, and all of them must match for the rule to
+/// match a window. A window without the property, e.g. a Wayland window without a class, does not
+/// match a rule which specifies it.
+#[derive(Clone, Debug)]
+pub struct WindowRule {
+    app_id: Option<Regex>,
+    class: Option<Regex>,
+    title: Option<Regex>,
+}
+
+impl WindowRule {
+    /// Creates a new `WindowRule` from its configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the rule.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `WindowRule`, or an error if a regular expression is invalid or
+    /// the rule does not specify any property.
+    pub fn from_config(config: &WindowRuleConfig) -> Result<Self> {
+        let compile = |re: &Option<String>| re.as_deref().map(Regex::new).transpose();
+        let rule = Self {
+            app_id: compile(&config.app_id)?,
+            class: compile(&config.class)?,
+            title: compile(&config.title)?,
+        };
+        if rule.app_id.is_none() && rule.class.is_none() && rule.title.is_none() {
+            return Err(anyhow::anyhow!(
+                "Window rule needs at least one of `app-id`, `class` or `title`"
+            ));
+        }
+        Ok(rule)
+    }
+
+    /// Checks whether the rule matches a window.
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        let matches = |re: &Option<Regex>, value: &Option<String>| match (re, value) {
+            (None, _) => true,
+            (Some(re), Some(value)) => re.is_match(value),
+            (Some(_), None) => false,
+        };
+        matches(&self.app_id, &window.app_id)
+            && matches(&self.class, &window.class)
+            && matches(&self.title, &window.title)
+    }
+}
+
+/// Checks whether any of the rules matches a window.
+pub fn any_matches(rules: &[WindowRule], window: &WindowInfo) -> bool {
+    rules.iter().any(|rule| rule.matches(window))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_id: Option<&str>, class: Option<&str>, title: &str) -> WindowInfo {
+        WindowInfo {
+            id: 1,
+            workspace: "1".to_string(),
+            floating: false,
+            focused: false,
+            fullscreen: false,
+            app_id: app_id.map(str::to_string),
+            class: class.map(str::to_string),
+            title: Some(title.to_string()),
+        }
+    }
+
+    fn rule(app_id: Option<&str>, class: Option<&str>, title: Option<&str>) -> WindowRule {
+        WindowRule::from_config(&WindowRuleConfig {
+            app_id: app_id.map(str::to_string),
+            class: class.map(str::to_string),
+            title: title.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let chat = window(Some("org.telegram.desktop"), None, "Telegram");
+        let browser = window(None, Some("firefox"), "YouTube - Mozilla Firefox");
+
+        assert!(rule(Some("telegram"), None, None).matches(&chat));
+        assert!(!rule(Some("telegram"), None, None).matches(&browser));
+        assert!(rule(None, Some("^firefox$"), Some("YouTube")).matches(&browser));
+        assert!(!rule(None, Some("^firefox$"), Some("GitHub")).matches(&browser));
+        // The class is not set for Wayland windows
+        assert!(!rule(None, Some("Telegram"), None).matches(&chat));
+    }
+
+    #[test]
+    fn test_rule_invalid() {
+        let empty = WindowRuleConfig {
+            app_id: None,
+            class: None,
+            title: None,
+        };
+        assert!(WindowRule::from_config(&empty).is_err());
+        let invalid = WindowRuleConfig {
+            app_id: Some("(".to_string()),
+            class: None,
+            title: None,
+        };
+        assert!(WindowRule::from_config(&invalid).is_err());
+    }
+}